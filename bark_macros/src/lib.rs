@@ -0,0 +1,147 @@
+//! Procedural macros for the `bark` crate; use them through `bark::quote!`.
+
+#![allow(clippy::needless_arbitrary_self_type)]
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+// The macro has to check Bark syntax at compile time, but cannot depend on
+// the `bark` crate that re-exports it, so the front end is compiled in here.
+// It is linted as part of `bark`, and none of the optional features of `bark`
+// are enabled here.
+#[allow(dead_code, unexpected_cfgs, clippy::all)]
+#[path = "../../src/lexer.rs"]
+mod lexer;
+#[allow(dead_code, clippy::all)]
+#[path = "../../src/parser.rs"]
+mod parser;
+#[allow(dead_code, clippy::all)]
+#[path = "../../src/visit.rs"]
+mod visit;
+
+use visit::Visit;
+
+/// Prefix of the placeholder identifiers standing for splices; must match
+/// `bark::quote::SPLICE_PREFIX`.
+const SPLICE_PREFIX: &str = "__bark_splice_";
+
+fn splice_placeholder(index: usize) -> String {
+    format!("{}{}", SPLICE_PREFIX, index)
+}
+
+/// Counts the placeholders that stand where an expression does, and so are
+/// substituted by `bark::quote::expand`.
+struct Placeholders(usize);
+
+impl Visit for Placeholders {
+    fn visit_identifier(self: &mut Self, name: &[u8]) {
+        if name.starts_with(SPLICE_PREFIX.as_bytes()) {
+            self.0 += 1;
+        }
+    }
+}
+
+/// Turns the macro input back into Bark source, replacing every `#{expr}`
+/// splice with a placeholder identifier and collecting the Rust expressions.
+fn collect_source(input: TokenStream, source: &mut String, splices: &mut Vec<TokenStream>) -> Result<(), String> {
+    let mut trees = input.into_iter().peekable();
+    while let Some(tree) = trees.next() {
+        match tree {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                match trees.peek() {
+                    Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                        source.push_str(&splice_placeholder(splices.len()));
+                        source.push(' ');
+                        splices.push(group.stream());
+                        trees.next();
+                    },
+                    _ => {
+                        source.push('#');
+                        source.push(' ');
+                    },
+                }
+            },
+            TokenTree::Punct(punct) => {
+                source.push(punct.as_char());
+                if punct.spacing() == Spacing::Alone {
+                    source.push(' ');
+                }
+            },
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis  => ("(", ")"),
+                    Delimiter::Brace        => ("{", "}"),
+                    Delimiter::Bracket      => ("[", "]"),
+                    Delimiter::None         => ("", ""),
+                };
+                source.push_str(open);
+                source.push(' ');
+                collect_source(group.stream(), source, splices)?;
+                source.push_str(close);
+                source.push(' ');
+            },
+            TokenTree::Ident(ident) if ident.to_string().starts_with(SPLICE_PREFIX) => {
+                return Err(format!("`{}` is reserved for splices", ident));
+            },
+            TokenTree::Ident(_) | TokenTree::Literal(_) => {
+                source.push_str(&tree.to_string());
+                source.push(' ');
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn string_literal(value: &str) -> String {
+    format!("{:?}", value)
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("::std::compile_error!({})", string_literal(message)).parse().unwrap()
+}
+
+/// Parses a Bark statement at compile time and expands to a
+/// `bark::parser::Statement`.
+///
+/// `#{expr}` splices in any Rust expression evaluating to an `Expression`:
+///
+/// ```ignore
+/// let value = bark::builder::ASTBuilder::integer(1);
+/// let ast = bark::quote!(let x = #{value} + 2;);
+/// ```
+///
+/// A splice can only stand where an expression can, not for a name such as
+/// the one bound by `let` or the member in `a.b`.
+///
+/// Rust drops `//` and the rest of its line as a comment before the macro
+/// sees it, so Bark's `//` and `//=` cannot be written inside `quote!`.
+#[proc_macro]
+pub fn quote(input: TokenStream) -> TokenStream {
+    let mut source = String::new();
+    let mut splices = vec![];
+    if let Err(message) = collect_source(input, &mut source, &mut splices) {
+        return compile_error(&message);
+    }
+
+    let checked = lexer::tokenize(source.as_bytes())
+        .map_err(|error| error.to_string())
+        .and_then(|tokens| parser::parse(&tokens).map_err(|error| error.to_string()));
+    let statement = match checked {
+        Ok(statement) => statement,
+        Err(message) => {
+            return compile_error(&format!("invalid Bark syntax in `{}`: {}", source.trim_end(), message));
+        },
+    };
+    let mut placeholders = Placeholders(0);
+    placeholders.visit_statement(&statement);
+    if placeholders.0 != splices.len() {
+        return compile_error(&format!("a splice must stand for an expression in `{}`", source.trim_end()));
+    }
+
+    let splices: Vec<String> = splices.iter()
+        .map(|splice| format!("({})", splice))
+        .collect();
+    format!("::bark::quote::expand({}, ::std::vec![{}])", string_literal(&source), splices.join(", "))
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,275 @@
+//! The AST types, re-exported from the parser, and a compact S-expression
+//! dump of them for golden tests.
+//!
+//! Every operation is a list headed by its operator as spelled in Bark, and
+//! every leaf is written as in the source:
+//!
+//! - `(let x value)`, `(let mut x value)`, `(let (a, b) value)` and
+//!   `(let x: type value)`,
+//!   `(= target value)`, `(; expression)` for an expression statement,
+//!   `(function f (a: type b) -> type (block ...))`,
+//!   the annotations only if there are any,
+//!   `(for x iterable (block ...))`, `(struct Name field...)`,
+//!   `(enum Name (Variant field...)...)`
+//! - `(+ a b)`, `(- a)`, `(not a)`, `(?? a b)`, `(== a b)` and so on for
+//!   every operator, unary ones having a single operand
+//! - `(call callee argument...)`, `(index object index)`, `(. object name)`
+//!   and `(?. object name)`
+//! - `(array element...)`, `(tuple element...)`, `(map (key value)...)`,
+//!   `(interpolate part...)`, and
+//!   `(block statement... value)`, the value only if there is one
+//! - `(new Name (field value)...)` for struct literals, and
+//!   `(if condition consequent alternative)`
+//! - identifiers, paths such as `io::open`, and literals such as `0xff`,
+//!   `1.5e3`, `'c'` and `"text\n"`
+//!
+//! The format only changes when the AST does.
+
+use crate::lexer::{render, Token};
+
+pub use crate::parser::{
+    Assignment, BinaryOperation, Block, Call, Conditional, Declaration, EnumDeclaration, Expression, For,
+    Function, Index, Member, Parameter, Pattern, Statement, StructDeclaration, StructLiteral, TypeExpr,
+    UnaryOperation, Variant,
+};
+
+struct Writer {
+    output: String,
+}
+
+impl Writer {
+    fn text(self: &mut Self, text: &[u8]) {
+        self.output.push_str(&String::from_utf8_lossy(text));
+    }
+
+    fn token(self: &mut Self, token: Token) {
+        self.text(&render(&[token]));
+    }
+
+    /// Writes `(head child...)`.
+    fn list<'a>(self: &mut Self, head: &str, children: impl IntoIterator<Item = &'a Expression>) {
+        self.output.push('(');
+        self.output.push_str(head);
+        for child in children {
+            self.output.push(' ');
+            self.expression(child);
+        }
+        self.output.push(')');
+    }
+
+    fn statement(self: &mut Self, statement: &Statement) {
+        match statement {
+            Statement::Let(declaration) => {
+                self.output.push_str(if declaration.mutable { "(let mut " } else { "(let " });
+                self.output.push_str(&declaration.pattern.to_string());
+                self.annotation(&declaration.annotation);
+                self.output.push(' ');
+                self.expression(&declaration.value);
+                self.output.push(')');
+            },
+            Statement::Function(function) => {
+                self.output.push_str("(function ");
+                self.text(&function.name);
+                self.output.push_str(" (");
+                for (i, parameter) in function.parameters.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push(' ');
+                    }
+                    self.text(&parameter.name);
+                    self.annotation(&parameter.annotation);
+                }
+                self.output.push(')');
+                if let Some(return_type) = &function.return_type {
+                    self.output.push_str(&format!(" -> {}", return_type));
+                }
+                self.output.push(' ');
+                self.block(&function.body);
+                self.output.push(')');
+            },
+            Statement::Assign(assignment) => {
+                self.list("=", [&assignment.target, &assignment.value]);
+            },
+            Statement::Expression(expression) => {
+                self.list(";", [&**expression]);
+            },
+            Statement::For(for_loop) => {
+                self.output.push_str("(for ");
+                self.text(&for_loop.binding);
+                self.output.push(' ');
+                self.expression(&for_loop.iterable);
+                self.output.push(' ');
+                self.block(&for_loop.body);
+                self.output.push(')');
+            },
+            Statement::Struct(declaration) => {
+                self.output.push_str("(struct ");
+                self.text(&declaration.name);
+                self.names(&declaration.fields);
+                self.output.push(')');
+            },
+            Statement::Enum(declaration) => {
+                self.output.push_str("(enum ");
+                self.text(&declaration.name);
+                for variant in declaration.variants.iter() {
+                    self.output.push_str(" (");
+                    self.text(&variant.name);
+                    self.names(&variant.fields);
+                    self.output.push(')');
+                }
+                self.output.push(')');
+            },
+        }
+    }
+
+    /// Writes `: type` if there is an annotation.
+    fn annotation(self: &mut Self, annotation: &Option<TypeExpr>) {
+        if let Some(annotation) = annotation {
+            self.output.push_str(&format!(": {}", annotation));
+        }
+    }
+
+    /// Writes each of `names` after a space.
+    fn names(self: &mut Self, names: &[Vec<u8>]) {
+        for name in names {
+            self.output.push(' ');
+            self.text(name);
+        }
+    }
+
+    fn member(self: &mut Self, head: &str, member: &Member) {
+        self.output.push('(');
+        self.output.push_str(head);
+        self.output.push(' ');
+        self.expression(&member.object);
+        self.output.push(' ');
+        self.text(&member.name);
+        self.output.push(')');
+    }
+
+    fn expression(self: &mut Self, expression: &Expression) {
+        match expression {
+            Expression::Identifier(name) => self.text(name),
+            Expression::Path(segments) => self.text(&segments.join(&b"::"[..])),
+            Expression::IntegerLiteral(integer) => self.token(Token::Integer(integer.clone())),
+            Expression::FloatLiteral(float) => self.token(Token::Float(float.clone())),
+            Expression::BooleanLiteral(boolean) => self.output.push_str(if *boolean { "true" } else { "false" }),
+            Expression::CharacterLiteral(character) => self.token(Token::Character(*character)),
+            Expression::StringLiteral(string) => self.token(Token::String(string.clone())),
+            Expression::InterpolatedString(parts) => self.list("interpolate", parts.iter()),
+            Expression::ArrayLiteral(elements) => self.list("array", elements.iter()),
+            Expression::TupleLiteral(elements) => self.list("tuple", elements.iter()),
+            Expression::MapLiteral(entries) => {
+                self.output.push_str("(map");
+                for (key, value) in entries.iter() {
+                    self.output.push_str(" (");
+                    self.expression(key);
+                    self.output.push(' ');
+                    self.expression(value);
+                    self.output.push(')');
+                }
+                self.output.push(')');
+            },
+            Expression::Call(call) => self.list("call", [&call.callee].into_iter().chain(call.arguments.iter())),
+            Expression::Index(index) => self.list("index", [&index.object, &index.index]),
+            Expression::MemberAccess(member) => self.member(".", member),
+            Expression::OptionalMember(member) => self.member("?.", member),
+            Expression::Block(block) => self.block(block),
+            Expression::Conditional(conditional) => {
+                self.list("if", [&conditional.condition, &conditional.consequent, &conditional.alternative]);
+            },
+            Expression::StructLiteral(literal) => {
+                self.output.push_str("(new ");
+                self.text(&literal.name.join(&b"::"[..]));
+                for (name, value) in literal.fields.iter() {
+                    self.output.push_str(" (");
+                    self.text(name);
+                    self.output.push(' ');
+                    self.expression(value);
+                    self.output.push(')');
+                }
+                self.output.push(')');
+            },
+            Expression::UnaryAddition(unary) => self.list("+", [&unary.operand]),
+            Expression::UnarySubtraction(unary) => self.list("-", [&unary.operand]),
+            Expression::LogicalNot(unary) => self.list("not", [&unary.operand]),
+            Expression::BitwiseNot(unary) => self.list("~", [&unary.operand]),
+            Expression::BinaryAddition(binary) => self.binary("+", binary),
+            Expression::BinarySubtraction(binary) => self.binary("-", binary),
+            Expression::BinaryMultiplication(binary) => self.binary("*", binary),
+            Expression::BinaryDivision(binary) => self.binary("/", binary),
+            Expression::BinaryFloorDivision(binary) => self.binary("//", binary),
+            Expression::BinaryRemainder(binary) => self.binary("%", binary),
+            Expression::LogicalAnd(binary) => self.binary("and", binary),
+            Expression::LogicalOr(binary) => self.binary("or", binary),
+            Expression::LogicalXor(binary) => self.binary("xor", binary),
+            Expression::BitwiseAnd(binary) => self.binary("&", binary),
+            Expression::BitwiseOr(binary) => self.binary("|", binary),
+            Expression::BitwiseXor(binary) => self.binary("^", binary),
+            Expression::BitwiseLeftShift(binary) => self.binary("<<", binary),
+            Expression::BitwiseRightShift(binary) => self.binary(">>", binary),
+            Expression::NullCoalescing(binary) => self.binary("??", binary),
+            Expression::Equals(binary) => self.binary("==", binary),
+            Expression::NotEquals(binary) => self.binary("!=", binary),
+            Expression::LessThan(binary) => self.binary("<", binary),
+            Expression::LessThanOrEquals(binary) => self.binary("<=", binary),
+            Expression::GreaterThan(binary) => self.binary(">", binary),
+            Expression::GreaterThanOrEquals(binary) => self.binary(">=", binary),
+        }
+    }
+
+    fn block(self: &mut Self, block: &Block) {
+        self.output.push_str("(block");
+        for statement in block.statements.iter() {
+            self.output.push(' ');
+            self.statement(statement);
+        }
+        if let Some(value) = &block.value {
+            self.output.push(' ');
+            self.expression(value);
+        }
+        self.output.push(')');
+    }
+
+    fn binary(self: &mut Self, operator: &str, operation: &BinaryOperation) {
+        self.list(operator, [&operation.left_operand, &operation.right_operand]);
+    }
+}
+
+pub fn to_sexpr(ast: &Statement) -> String {
+    let mut writer = Writer { output: String::new() };
+    writer.statement(ast);
+    writer.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn sexpr(script: &[u8]) -> String {
+        to_sexpr(&parse(&tokenize(script).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn dump() {
+        assert_eq!(sexpr(b"let x = 2 * 3 + -y;"), "(let x (+ (* 2 3) (- y)))");
+        assert_eq!(sexpr(b"let mut s = \"a${b}\\n\";"), "(let mut s (interpolate \"a\" b \"\\n\"))");
+        assert_eq!(sexpr(b"a.b[0] = f(1.5e3, [], io::c?.d);"), "(= (index (. a b) 0) (call f 1.5e3 (array) (?. io::c d)))");
+        assert_eq!(
+            sexpr(b"x //= { let y = 'z'; y == 0xff and not w };"),
+            "(= x (// x (block (let y 'z') (and (== y 0xff) (not w)))))",
+        );
+        assert_eq!(sexpr(b"let x: [geo::Point] = [];"), "(let x: [geo::Point] (array))");
+        assert_eq!(sexpr(b"function f(a: int, b) -> float { a }"), "(function f (a: int b) -> float (block a))");
+        assert_eq!(sexpr(b"function g() {}"), "(function g () (block))");
+        assert_eq!(sexpr(b"let (a, (b,)): (int, (float,)) = (1, (2.5,));"), "(let (a, (b,)): (int, (float,)) (tuple 1 (tuple 2.5)))");
+        assert_eq!(sexpr(b"let m = { a: {:}, 1: 'c' };"), "(let m (map (\"a\" (map)) (1 'c')))");
+        assert_eq!(sexpr(b"let x = if a then b else if c then d else e;"), "(let x (if a b (if c d e)))");
+        assert_eq!(sexpr(b"struct Point { x, y }"), "(struct Point x y)");
+        assert_eq!(sexpr(b"enum Shape { Circle(r), Empty }"), "(enum Shape (Circle r) (Empty))");
+        assert_eq!(sexpr(b"let p = geo::Point { x: 1, y: -y };"), "(let p (new geo::Point (x 1) (y (- y))))");
+        assert_eq!(sexpr(b"f(x);"), "(; (call f x))");
+        assert_eq!(sexpr(b"let y = { g(); 1 };"), "(let y (block (; (call g)) 1))");
+    }
+}
@@ -0,0 +1,402 @@
+//! Programmatic construction of token streams and ASTs.
+//!
+//! The builders check the invariants the lexer and parser would otherwise
+//! guarantee, such as identifiers not colliding with keywords, so generated
+//! code can be handed to any consumer of `lexer::Token` or the AST.
+
+use std::fmt;
+use crate::lexer::{tokenize, Token, TokenKind};
+use crate::parser::{
+    binary_constructor, unary_constructor, Assignment, BinaryOperation, Block, Call, Conditional, Declaration,
+    EnumDeclaration, Expression, For, Function, Index, Member, Parameter, Pattern, Statement,
+    StructDeclaration, StructLiteral, TypeExpr, UnaryOperation, Variant,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum BuildError {
+    InvalidIdentifier(String),
+    InvalidFloat(f64),
+    /// The kind carries a payload, so it needs its dedicated method.
+    UnsupportedTokenKind(TokenKind),
+    UnsupportedOperator(TokenKind),
+    /// Map keys can only be literals.
+    InvalidMapKey,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidIdentifier(name) =>
+                write!(f, "`{}` is not a valid identifier", name),
+            BuildError::InvalidFloat(value) =>
+                write!(f, "{} cannot be written as a float literal", value),
+            BuildError::UnsupportedTokenKind(kind) =>
+                write!(f, "{} cannot be built without a payload", kind),
+            BuildError::UnsupportedOperator(kind) =>
+                write!(f, "{} is not an operator of this arity", kind),
+            BuildError::InvalidMapKey =>
+                write!(f, "map keys must be literals"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Lexes `source`, expecting exactly one token.
+fn single_token(source: &[u8]) -> Option<Token> {
+    let mut tokens = tokenize(source).ok()?;
+    if tokens.len() == 1 {
+        tokens.pop()
+    } else {
+        None
+    }
+}
+
+fn identifier(name: &str) -> Result<Token, BuildError> {
+    match single_token(name.as_bytes()) {
+        Some(token @ Token::Identifier(_)) => Ok(token),
+        _ => Err(BuildError::InvalidIdentifier(name.to_string())),
+    }
+}
+
+fn integer(value: u64) -> Token {
+    match single_token(value.to_string().as_bytes()) {
+        Some(token @ Token::Integer(_)) => token,
+        _ => unreachable!(),
+    }
+}
+
+/// Literals are unsigned in the grammar, so negative values must be built
+/// with a unary minus.
+fn float(value: f64) -> Result<Token, BuildError> {
+    if !value.is_finite() || value.is_sign_negative() {
+        return Err(BuildError::InvalidFloat(value));
+    }
+    match single_token(format!("{:?}", value).as_bytes()) {
+        Some(token @ Token::Float(_)) => Ok(token),
+        _ => Err(BuildError::InvalidFloat(value)),
+    }
+}
+
+#[derive(Default)]
+pub struct TokenBuilder {
+    tokens: Vec<Token>,
+}
+
+impl TokenBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a token that has no payload, such as a keyword or operator.
+    pub fn push(self: &mut Self, kind: TokenKind) -> Result<&mut Self, BuildError> {
+        let token = kind.spelling()
+            .and_then(|spelling| single_token(spelling.as_bytes()))
+            .ok_or(BuildError::UnsupportedTokenKind(kind))?;
+        self.tokens.push(token);
+        Ok(self)
+    }
+
+    pub fn identifier(self: &mut Self, name: &str) -> Result<&mut Self, BuildError> {
+        self.tokens.push(identifier(name)?);
+        Ok(self)
+    }
+
+    pub fn integer(self: &mut Self, value: u64) -> &mut Self {
+        self.tokens.push(integer(value));
+        self
+    }
+
+    pub fn float(self: &mut Self, value: f64) -> Result<&mut Self, BuildError> {
+        self.tokens.push(float(value)?);
+        Ok(self)
+    }
+
+    pub fn character(self: &mut Self, value: char) -> &mut Self {
+        self.tokens.push(Token::Character(value));
+        self
+    }
+
+    pub fn string(self: &mut Self, value: &[u8]) -> &mut Self {
+        self.tokens.push(Token::String(Box::new(value.to_vec())));
+        self
+    }
+
+    pub fn build(self: &mut Self) -> Vec<Token> {
+        std::mem::take(&mut self.tokens)
+    }
+}
+
+pub struct ASTBuilder;
+
+impl ASTBuilder {
+    pub fn identifier(name: &str) -> Result<Expression, BuildError> {
+        let Token::Identifier(name) = identifier(name)? else { unreachable!() };
+        Ok(Expression::Identifier(name))
+    }
+
+    /// Builds `a::b::c` from its segments; a single segment builds a plain
+    /// identifier.
+    pub fn path(segments: &[&str]) -> Result<Expression, BuildError> {
+        let mut names = vec![];
+        for segment in segments {
+            let Token::Identifier(name) = identifier(segment)? else { unreachable!() };
+            names.push(*name);
+        }
+        match names.len() {
+            0 => Err(BuildError::InvalidIdentifier(String::new())),
+            1 => Ok(Expression::Identifier(Box::new(names.pop().unwrap()))),
+            _ => Ok(Expression::Path(Box::new(names))),
+        }
+    }
+
+    pub fn integer(value: u64) -> Expression {
+        let Token::Integer(integer) = integer(value) else { unreachable!() };
+        Expression::IntegerLiteral(integer)
+    }
+
+    pub fn float(value: f64) -> Result<Expression, BuildError> {
+        let Token::Float(float) = float(value)? else { unreachable!() };
+        Ok(Expression::FloatLiteral(float))
+    }
+
+    pub fn boolean(value: bool) -> Expression {
+        Expression::BooleanLiteral(value)
+    }
+
+    pub fn character(value: char) -> Expression {
+        Expression::CharacterLiteral(value)
+    }
+
+    pub fn string(value: &[u8]) -> Expression {
+        Expression::StringLiteral(Box::new(value.to_vec()))
+    }
+
+    pub fn interpolated_string(parts: Vec<Expression>) -> Expression {
+        Expression::InterpolatedString(Box::new(parts))
+    }
+
+    pub fn array(elements: Vec<Expression>) -> Expression {
+        Expression::ArrayLiteral(Box::new(elements))
+    }
+
+    pub fn map(entries: Vec<(Expression, Expression)>) -> Result<Expression, BuildError> {
+        for (key, _) in entries.iter() {
+            if !matches!(key, Expression::IntegerLiteral(_) | Expression::FloatLiteral(_) | Expression::BooleanLiteral(_)
+                | Expression::CharacterLiteral(_) | Expression::StringLiteral(_)) {
+                return Err(BuildError::InvalidMapKey);
+            }
+        }
+        Ok(Expression::MapLiteral(Box::new(entries)))
+    }
+
+    pub fn tuple(elements: Vec<Expression>) -> Expression {
+        Expression::TupleLiteral(Box::new(elements))
+    }
+
+    /// Builds the prefix operation spelled by `operator`, e.g. `TokenKind::Tilde`.
+    pub fn unary(operator: TokenKind, operand: Expression) -> Result<Expression, BuildError> {
+        let constructor = unary_constructor(operator)
+            .ok_or(BuildError::UnsupportedOperator(operator))?;
+        Ok(constructor(Box::new(UnaryOperation { operand })))
+    }
+
+    /// Builds the binary operation spelled by `operator`, e.g. `TokenKind::Plus`.
+    pub fn binary(operator: TokenKind, left_operand: Expression, right_operand: Expression) -> Result<Expression, BuildError> {
+        let constructor = binary_constructor(operator)
+            .ok_or(BuildError::UnsupportedOperator(operator))?;
+        Ok(constructor(Box::new(BinaryOperation { left_operand, right_operand })))
+    }
+
+    pub fn call(callee: Expression, arguments: Vec<Expression>) -> Expression {
+        Expression::Call(Box::new(Call { callee, arguments }))
+    }
+
+    pub fn index(object: Expression, index: Expression) -> Expression {
+        Expression::Index(Box::new(Index { object, index }))
+    }
+
+    pub fn member_access(object: Expression, name: &str) -> Result<Expression, BuildError> {
+        let Token::Identifier(name) = identifier(name)? else { unreachable!() };
+        Ok(Expression::MemberAccess(Box::new(Member { object, name: name.to_vec() })))
+    }
+
+    pub fn optional_member(object: Expression, name: &str) -> Result<Expression, BuildError> {
+        let Token::Identifier(name) = identifier(name)? else { unreachable!() };
+        Ok(Expression::OptionalMember(Box::new(Member { object, name: name.to_vec() })))
+    }
+
+    pub fn conditional(condition: Expression, consequent: Expression, alternative: Expression) -> Expression {
+        Expression::Conditional(Box::new(Conditional { condition, consequent, alternative }))
+    }
+
+    pub fn block(statements: Vec<Statement>, value: Option<Expression>) -> Expression {
+        Expression::Block(Box::new(Block { statements, value }))
+    }
+
+    /// Builds `name { field: value, ... }`, the struct being named by a path
+    /// of one or more `name` segments.
+    pub fn struct_literal(name: &[&str], fields: Vec<(&str, Expression)>) -> Result<Expression, BuildError> {
+        let mut segments = vec![];
+        for segment in name {
+            let Token::Identifier(segment) = identifier(segment)? else { unreachable!() };
+            segments.push(*segment);
+        }
+        if segments.is_empty() {
+            return Err(BuildError::InvalidIdentifier(String::new()));
+        }
+        let mut values = vec![];
+        for (field, value) in fields {
+            let Token::Identifier(field) = identifier(field)? else { unreachable!() };
+            values.push((*field, value));
+        }
+        Ok(Expression::StructLiteral(Box::new(StructLiteral { name: segments, fields: values })))
+    }
+
+    pub fn declare(name: &str, mutable: bool, value: Expression) -> Result<Statement, BuildError> {
+        Self::declare_typed(name, mutable, None, value)
+    }
+
+    /// Builds `let name: annotation = value`, or a plain declaration without
+    /// an annotation.
+    pub fn declare_typed(
+        name: &str,
+        mutable: bool,
+        annotation: Option<TypeExpr>,
+        value: Expression,
+    ) -> Result<Statement, BuildError> {
+        Ok(Self::destructure(Self::identifier_pattern(name)?, mutable, annotation, value))
+    }
+
+    /// Builds `let pattern: annotation = value`, or a declaration without an
+    /// annotation.
+    pub fn destructure(pattern: Pattern, mutable: bool, annotation: Option<TypeExpr>, value: Expression) -> Statement {
+        Statement::Let(Box::new(Declaration { pattern, mutable, annotation, value }))
+    }
+
+    pub fn identifier_pattern(name: &str) -> Result<Pattern, BuildError> {
+        let Token::Identifier(name) = identifier(name)? else { unreachable!() };
+        Ok(Pattern::Identifier(*name))
+    }
+
+    pub fn tuple_pattern(elements: Vec<Pattern>) -> Pattern {
+        Pattern::Tuple(elements)
+    }
+
+    /// Builds the type named by `segments`, e.g. `&["int"]`.
+    pub fn named_type(segments: &[&str]) -> Result<TypeExpr, BuildError> {
+        let mut names = vec![];
+        for segment in segments {
+            let Token::Identifier(name) = identifier(segment)? else { unreachable!() };
+            names.push(*name);
+        }
+        if names.is_empty() {
+            return Err(BuildError::InvalidIdentifier(String::new()));
+        }
+        Ok(TypeExpr::Named(names))
+    }
+
+    pub fn array_type(element: TypeExpr) -> TypeExpr {
+        TypeExpr::Array(Box::new(element))
+    }
+
+    pub fn tuple_type(elements: Vec<TypeExpr>) -> TypeExpr {
+        TypeExpr::Tuple(elements)
+    }
+
+    /// Builds a function from its parameters, each given by its name and
+    /// optional annotation.
+    pub fn function(
+        name: &str,
+        parameters: Vec<(&str, Option<TypeExpr>)>,
+        return_type: Option<TypeExpr>,
+        body: Block,
+    ) -> Result<Statement, BuildError> {
+        let Token::Identifier(name) = identifier(name)? else { unreachable!() };
+        let mut built = vec![];
+        for (parameter, annotation) in parameters {
+            let Token::Identifier(parameter) = identifier(parameter)? else { unreachable!() };
+            built.push(Parameter { name: *parameter, annotation });
+        }
+        Ok(Statement::Function(Box::new(Function { name: *name, parameters: built, return_type, body })))
+    }
+
+    pub fn assign(name: &str, value: Expression) -> Result<Statement, BuildError> {
+        Ok(Statement::Assign(Box::new(Assignment { target: Self::identifier(name)?, value })))
+    }
+
+    /// Builds the statement `value;`.
+    pub fn expression_statement(value: Expression) -> Statement {
+        Statement::Expression(Box::new(value))
+    }
+
+    pub fn for_loop(binding: &str, iterable: Expression, body: Block) -> Result<Statement, BuildError> {
+        let Token::Identifier(binding) = identifier(binding)? else { unreachable!() };
+        Ok(Statement::For(Box::new(For { binding: binding.to_vec(), iterable, body })))
+    }
+
+    pub fn struct_declaration(name: &str, fields: &[&str]) -> Result<Statement, BuildError> {
+        let Token::Identifier(name) = identifier(name)? else { unreachable!() };
+        let mut names = vec![];
+        for field in fields {
+            let Token::Identifier(field) = identifier(field)? else { unreachable!() };
+            names.push(*field);
+        }
+        Ok(Statement::Struct(Box::new(StructDeclaration { name: *name, fields: names })))
+    }
+
+    /// Builds an enum from its variants, each given by its name and the
+    /// names of its fields.
+    pub fn enum_declaration(name: &str, variants: &[(&str, &[&str])]) -> Result<Statement, BuildError> {
+        let Token::Identifier(name) = identifier(name)? else { unreachable!() };
+        let mut built = vec![];
+        for (variant, fields) in variants {
+            let Token::Identifier(variant) = identifier(variant)? else { unreachable!() };
+            let mut names = vec![];
+            for field in fields.iter() {
+                let Token::Identifier(field) = identifier(field)? else { unreachable!() };
+                names.push(*field);
+            }
+            built.push(Variant { name: *variant, fields: names });
+        }
+        Ok(Statement::Enum(Box::new(EnumDeclaration { name: *name, variants: built })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ast_to_json;
+    use crate::parser::parse;
+
+    #[test]
+    fn test() {
+        let tokens = TokenBuilder::new()
+            .push(TokenKind::Let).unwrap()
+            .identifier("x").unwrap()
+            .push(TokenKind::Assign).unwrap()
+            .integer(40)
+            .push(TokenKind::Plus).unwrap()
+            .float(2.5e-3).unwrap()
+            .build();
+        assert_eq!(tokens, tokenize(b"let x = 40 + 0.0025").unwrap());
+
+        assert_eq!(TokenBuilder::new().identifier("let").err(),
+            Some(BuildError::InvalidIdentifier("let".to_string())));
+        assert_eq!(TokenBuilder::new().identifier("a b").err(),
+            Some(BuildError::InvalidIdentifier("a b".to_string())));
+        assert_eq!(TokenBuilder::new().float(-1.0).err(), Some(BuildError::InvalidFloat(-1.0)));
+        assert_eq!(TokenBuilder::new().push(TokenKind::Integer).err(),
+            Some(BuildError::UnsupportedTokenKind(TokenKind::Integer)));
+
+        let ast = ASTBuilder::declare("x", false, ASTBuilder::binary(
+            TokenKind::Asterisk,
+            ASTBuilder::unary(TokenKind::Tilde, ASTBuilder::identifier("y").unwrap()).unwrap(),
+            ASTBuilder::integer(3),
+        ).unwrap()).unwrap();
+        let parsed = parse(&tokenize(b"let x = ~y * 3;").unwrap()).unwrap();
+        assert_eq!(ast_to_json(&[ast]), ast_to_json(&[parsed]));
+
+        assert!(ASTBuilder::binary(TokenKind::Comma, ASTBuilder::integer(1), ASTBuilder::integer(2)).is_err());
+    }
+}
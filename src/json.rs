@@ -0,0 +1,1166 @@
+//! JSON dump of the AST, as printed by `bark ast --json`.
+//!
+//! The format is versioned by [`SCHEMA_VERSION`] and does not follow the
+//! names of the Rust types, so it stays stable while the parser evolves.
+//! A document has the shape `{ "version": 3, "ast": [<statement>, ...] }`,
+//! holding the statements of a whole script in source order, and every node
+//! is an object with a snake_case `"kind"` plus kind-specific
+//! fields. Statements are:
+//!
+//! - `let`: `"name"`, or a `"pattern"` when it destructures, `"mutable"`,
+//!   `"value"` and, if it is annotated, `"type"`
+//! - `assign`: `"children"`, the target and the value
+//! - `expression`: `"value"`, an expression evaluated for its effects
+//! - `for`: `"binding"`, `"iterable"` and `"body"`, a `block`
+//! - `function`: `"name"`, `"parameters"`, an array of objects with a
+//!   `"name"` and an optional `"type"`, `"body"`, a `block`, and, if it is
+//!   annotated, `"return_type"`
+//! - `struct`: `"name"` and `"fields"`, an array of names
+//! - `enum`: `"name"` and `"variants"`, an array of objects with a `"name"`
+//!   and `"fields"`, an array of names
+//!
+//! Expressions are:
+//!
+//! - `identifier`: `"name"`
+//! - `path`: `"segments"`, an array of names
+//! - `integer_literal`: `"base"` (`decimal`, `hexadecimal`, `octal` or
+//!   `binary`) and `"digits"`
+//! - `float_literal`: `"integer"`, `"fractional"` and, in scientific
+//!   notation, a signed `"exponent"`, all as decimal digit strings
+//! - `boolean_literal`: `"value"`, a JSON boolean
+//! - `character_literal`: `"value"`
+//! - `string_literal`: `"value"`, or `"bytes"` (an array of numbers) when
+//!   the contents are not valid UTF-8
+//! - `interpolated_string`: `"children"`, the string literals and embedded
+//!   expressions in source order
+//! - `array_literal` and `tuple_literal`: `"elements"`
+//! - `map_literal`: `"entries"`, an array of objects with a `"key"`, a
+//!   literal, and a `"value"`
+//! - `call`: `"callee"` and `"arguments"`
+//! - `index`: `"object"` and `"index"`
+//! - `member_access` and `optional_member`: `"object"` and `"name"`
+//! - `conditional`: `"condition"`, `"consequent"` and `"alternative"`
+//! - `block`: `"statements"` and, if the block ends with an expression,
+//!   `"value"`
+//! - `struct_literal`: `"segments"`, the path naming the struct, and
+//!   `"fields"`, an array of objects with a `"name"` and a `"value"`
+//! - operators: `"children"`, the operands in source order
+//!
+//! Types are `named_type`, with the `"segments"` of its name, `array_type`,
+//! with the type of its `"element"`, and `tuple_type`, with its
+//! `"elements"`. Patterns are `identifier_pattern`, with a `"name"`, and
+//! `tuple_pattern`, with its `"elements"`.
+//!
+//! When dumped from a script, as by `bark ast --json`, every statement and
+//! expression also has a `"span"` right after its `"kind"`: the byte offsets
+//! `[start, end]` of the source it was parsed from. Desugared nodes point at
+//! the source they stand for, e.g. the `x + 1` of `x += 1` spans the whole
+//! assignment. [`ast_from_json`] does not read spans back.
+//!
+//! Adding fields is a compatible change; renaming or removing them bumps the
+//! version.
+//!
+//! [`ast_from_json`] reads documents of the current version back, so other
+//! front ends can produce ASTs. Unknown fields are ignored, but every node is
+//! checked against the invariants the parser guarantees.
+
+use std::fmt;
+use std::ops::Range;
+use crate::builder::ASTBuilder;
+use crate::lexer::{IntegerRepresentation, FloatRepresentation, Sign};
+use crate::parser::{
+    Assignment, BinaryConstructor, BinaryOperation, Block, Call, Conditional, Declaration, EnumDeclaration,
+    Expression, For, Function, Index, Member, Pattern, Statement, StructDeclaration, StructLiteral, TypeExpr,
+    UnaryConstructor, UnaryOperation,
+};
+
+pub const SCHEMA_VERSION: u32 = 3;
+
+enum Json {
+    Boolean(bool),
+    Number(u32),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+    /// The object of a statement or an expression.
+    Node(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn write(self: &Self, output: &mut String, indent: usize) {
+        match self {
+            Json::Boolean(boolean) => {
+                output.push_str(if *boolean { "true" } else { "false" });
+            },
+            Json::Number(number) => {
+                output.push_str(&number.to_string());
+            },
+            Json::String(string) => {
+                write_string(output, string);
+            },
+            Json::Array(items) => {
+                if items.len() == 0 {
+                    output.push_str("[]");
+                    return;
+                }
+                output.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        output.push(',');
+                    }
+                    write_newline(output, indent + 1);
+                    item.write(output, indent + 1);
+                }
+                write_newline(output, indent);
+                output.push(']');
+            },
+            Json::Object(fields) | Json::Node(fields) => {
+                output.push('{');
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        output.push(',');
+                    }
+                    write_newline(output, indent + 1);
+                    write_string(output, name);
+                    output.push_str(": ");
+                    value.write(output, indent + 1);
+                }
+                write_newline(output, indent);
+                output.push('}');
+            },
+        }
+    }
+
+    /// Inserts the next of `spans` into every node, walking them in the
+    /// order they were parsed in.
+    fn add_spans<'a>(self: &mut Self, spans: &mut impl Iterator<Item = &'a Range<usize>>) {
+        match self {
+            Json::Node(fields) => {
+                let span = spans.next().expect("every node has a span");
+                let span = Json::Array(vec![Json::Number(span.start as u32), Json::Number(span.end as u32)]);
+                fields.insert(1, ("span", span));
+                for (_, value) in fields.iter_mut().skip(2) {
+                    value.add_spans(spans);
+                }
+            },
+            Json::Object(fields) => {
+                for (_, value) in fields.iter_mut() {
+                    value.add_spans(spans);
+                }
+            },
+            Json::Array(items) => {
+                for item in items.iter_mut() {
+                    item.add_spans(spans);
+                }
+            },
+            Json::Boolean(_) | Json::Number(_) | Json::String(_) => {},
+        }
+    }
+}
+
+/// Marks the object of a statement or an expression as a node.
+fn into_node(json: Json) -> Json {
+    let Json::Object(fields) = json else { unreachable!() };
+    Json::Node(fields)
+}
+
+fn write_newline(output: &mut String, indent: usize) {
+    output.push('\n');
+    for _ in 0..indent {
+        output.push_str("  ");
+    }
+}
+
+fn write_string(output: &mut String, string: &str) {
+    output.push('"');
+    for character in string.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\0'..='\u{1f}' => output.push_str(&format!("\\u{:04x}", character as u32)),
+            _ => output.push(character),
+        }
+    }
+    output.push('"');
+}
+
+fn digits(digits: &[u8]) -> Json {
+    Json::String(digits.iter()
+        .map(|&digit| char::from_digit(digit as u32, 16).unwrap())
+        .collect())
+}
+
+fn bytes(kind: &'static str, name: &'static str, value: &[u8]) -> Json {
+    let value = match std::str::from_utf8(value) {
+        Ok(value) => (name, Json::String(value.to_string())),
+        Err(_) => ("bytes", Json::Array(value.iter().map(|&byte| Json::Number(byte as u32)).collect())),
+    };
+    Json::Object(vec![("kind", Json::String(kind.to_string())), value])
+}
+
+fn operation(kind: &'static str, children: Vec<&Expression>) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String(kind.to_string())),
+        ("children", Json::Array(children.into_iter().map(node).collect())),
+    ])
+}
+
+fn integer(integer: &IntegerRepresentation) -> Json {
+    let (base, value) = match integer {
+        IntegerRepresentation::Decimal(value)       => ("decimal", value),
+        IntegerRepresentation::Hexadecimal(value)   => ("hexadecimal", value),
+        IntegerRepresentation::Octal(value)         => ("octal", value),
+        IntegerRepresentation::Binary(value)        => ("binary", value),
+    };
+    Json::Object(vec![
+        ("kind", Json::String("integer_literal".to_string())),
+        ("base", Json::String(base.to_string())),
+        ("digits", digits(value)),
+    ])
+}
+
+fn float(float: &FloatRepresentation) -> Json {
+    let mut fields = vec![("kind", Json::String("float_literal".to_string()))];
+    match float {
+        FloatRepresentation::Decimal { integer, fractional } => {
+            fields.push(("integer", digits(integer)));
+            fields.push(("fractional", digits(fractional)));
+        },
+        FloatRepresentation::Scientific { integer, fractional, exponent_sign, exponent } => {
+            fields.push(("integer", digits(integer)));
+            fields.push(("fractional", digits(fractional)));
+            let Json::String(mut exponent) = digits(exponent) else { unreachable!() };
+            if let Sign::Negative = exponent_sign {
+                exponent.insert(0, '-');
+            }
+            fields.push(("exponent", Json::String(exponent)));
+        },
+    }
+    Json::Object(fields)
+}
+
+fn names(names: &[Vec<u8>]) -> Json {
+    Json::Array(names.iter().map(|name| Json::String(String::from_utf8_lossy(name).into_owned())).collect())
+}
+
+fn path(segments: &[Vec<u8>]) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String("path".to_string())),
+        ("segments", names(segments)),
+    ])
+}
+
+fn struct_literal(literal: &StructLiteral) -> Json {
+    let fields = literal.fields.iter()
+        .map(|(name, value)| Json::Object(vec![
+            ("name", Json::String(String::from_utf8_lossy(name).into_owned())),
+            ("value", node(value)),
+        ]))
+        .collect();
+    Json::Object(vec![
+        ("kind", Json::String("struct_literal".to_string())),
+        ("segments", names(&literal.name)),
+        ("fields", Json::Array(fields)),
+    ])
+}
+
+fn expression_statement(value: &Expression) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String("expression".to_string())),
+        ("value", node(value)),
+    ])
+}
+
+fn call(call: &Call) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String("call".to_string())),
+        ("callee", node(&call.callee)),
+        ("arguments", Json::Array(call.arguments.iter().map(node).collect())),
+    ])
+}
+
+fn array(kind: &'static str, elements: &[Expression]) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String(kind.to_string())),
+        ("elements", Json::Array(elements.iter().map(node).collect())),
+    ])
+}
+
+fn map(entries: &[(Expression, Expression)]) -> Json {
+    let entries = entries.iter()
+        .map(|(key, value)| Json::Object(vec![("key", node(key)), ("value", node(value))]))
+        .collect();
+    Json::Object(vec![
+        ("kind", Json::String("map_literal".to_string())),
+        ("entries", Json::Array(entries)),
+    ])
+}
+
+fn conditional(conditional: &Conditional) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String("conditional".to_string())),
+        ("condition", node(&conditional.condition)),
+        ("consequent", node(&conditional.consequent)),
+        ("alternative", node(&conditional.alternative)),
+    ])
+}
+
+fn index(index: &Index) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String("index".to_string())),
+        ("object", node(&index.object)),
+        ("index", node(&index.index)),
+    ])
+}
+
+fn member(kind: &'static str, member: &Member) -> Json {
+    Json::Object(vec![
+        ("kind", Json::String(kind.to_string())),
+        ("object", node(&member.object)),
+        ("name", Json::String(String::from_utf8_lossy(&member.name).into_owned())),
+    ])
+}
+
+fn block(block: &Block) -> Json {
+    let mut fields = vec![
+        ("kind", Json::String("block".to_string())),
+        ("statements", Json::Array(block.statements.iter().map(statement).collect())),
+    ];
+    if let Some(value) = &block.value {
+        fields.push(("value", node(value)));
+    }
+    Json::Object(fields)
+}
+
+fn for_loop(for_loop: &For) -> Json {
+    let Json::Object(mut fields) = bytes("for", "binding", &for_loop.binding) else { unreachable!() };
+    fields.push(("iterable", node(&for_loop.iterable)));
+    fields.push(("body", block(&for_loop.body)));
+    Json::Object(fields)
+}
+
+fn struct_declaration(declaration: &StructDeclaration) -> Json {
+    let Json::Object(mut fields) = bytes("struct", "name", &declaration.name) else { unreachable!() };
+    fields.push(("fields", names(&declaration.fields)));
+    Json::Object(fields)
+}
+
+fn enum_declaration(declaration: &EnumDeclaration) -> Json {
+    let variants = declaration.variants.iter()
+        .map(|variant| Json::Object(vec![
+            ("name", Json::String(String::from_utf8_lossy(&variant.name).into_owned())),
+            ("fields", names(&variant.fields)),
+        ]))
+        .collect();
+    let Json::Object(mut fields) = bytes("enum", "name", &declaration.name) else { unreachable!() };
+    fields.push(("variants", Json::Array(variants)));
+    Json::Object(fields)
+}
+
+fn type_expr(annotation: &TypeExpr) -> Json {
+    match annotation {
+        TypeExpr::Named(segments) => Json::Object(vec![
+            ("kind", Json::String("named_type".to_string())),
+            ("segments", names(segments)),
+        ]),
+        TypeExpr::Array(element) => Json::Object(vec![
+            ("kind", Json::String("array_type".to_string())),
+            ("element", type_expr(element)),
+        ]),
+        TypeExpr::Tuple(elements) => Json::Object(vec![
+            ("kind", Json::String("tuple_type".to_string())),
+            ("elements", Json::Array(elements.iter().map(type_expr).collect())),
+        ]),
+    }
+}
+
+fn pattern(binding: &Pattern) -> Json {
+    match binding {
+        Pattern::Identifier(name) => bytes("identifier_pattern", "name", name),
+        Pattern::Tuple(elements) => Json::Object(vec![
+            ("kind", Json::String("tuple_pattern".to_string())),
+            ("elements", Json::Array(elements.iter().map(pattern).collect())),
+        ]),
+    }
+}
+
+fn declaration(declaration: &Declaration) -> Json {
+    let mut fields = match &declaration.pattern {
+        Pattern::Identifier(name) => {
+            let Json::Object(fields) = bytes("let", "name", name) else { unreachable!() };
+            fields
+        },
+        Pattern::Tuple(_) => vec![
+            ("kind", Json::String("let".to_string())),
+            ("pattern", pattern(&declaration.pattern)),
+        ],
+    };
+    fields.push(("mutable", Json::Boolean(declaration.mutable)));
+    fields.push(("value", node(&declaration.value)));
+    if let Some(annotation) = &declaration.annotation {
+        fields.push(("type", type_expr(annotation)));
+    }
+    Json::Object(fields)
+}
+
+fn function(function: &Function) -> Json {
+    let parameters = function.parameters.iter()
+        .map(|parameter| {
+            let mut fields = vec![("name", Json::String(String::from_utf8_lossy(&parameter.name).into_owned()))];
+            if let Some(annotation) = &parameter.annotation {
+                fields.push(("type", type_expr(annotation)));
+            }
+            Json::Object(fields)
+        })
+        .collect();
+    let Json::Object(mut fields) = bytes("function", "name", &function.name) else { unreachable!() };
+    fields.push(("parameters", Json::Array(parameters)));
+    fields.push(("body", block(&function.body)));
+    if let Some(return_type) = &function.return_type {
+        fields.push(("return_type", type_expr(return_type)));
+    }
+    Json::Object(fields)
+}
+
+fn node(node: &Expression) -> Json {
+    into_node(match node {
+        Expression::Identifier(name)               => bytes("identifier", "name", name),
+        Expression::Path(segments)                 => path(segments),
+        Expression::IntegerLiteral(value)          => integer(value),
+        Expression::FloatLiteral(value)            => float(value),
+        Expression::BooleanLiteral(value)          => Json::Object(vec![
+            ("kind", Json::String("boolean_literal".to_string())),
+            ("value", Json::Boolean(*value)),
+        ]),
+        Expression::CharacterLiteral(value)        => Json::Object(vec![
+            ("kind", Json::String("character_literal".to_string())),
+            ("value", Json::String(value.to_string())),
+        ]),
+        Expression::StringLiteral(value)           => bytes("string_literal", "value", value),
+        Expression::InterpolatedString(parts)      => operation("interpolated_string", parts.iter().collect()),
+        Expression::ArrayLiteral(elements)         => array("array_literal", elements),
+        Expression::TupleLiteral(elements)         => array("tuple_literal", elements),
+        Expression::MapLiteral(entries)            => map(entries),
+        Expression::Conditional(value)             => conditional(value),
+        Expression::UnaryAddition(unary)           => operation("unary_addition", vec![&unary.operand]),
+        Expression::UnarySubtraction(unary)        => operation("unary_subtraction", vec![&unary.operand]),
+        Expression::LogicalNot(unary)              => operation("logical_not", vec![&unary.operand]),
+        Expression::BitwiseNot(unary)              => operation("bitwise_not", vec![&unary.operand]),
+        Expression::BinaryAddition(binary)         => operation("binary_addition", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BinarySubtraction(binary)      => operation("binary_subtraction", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BinaryMultiplication(binary)   => operation("binary_multiplication", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BinaryDivision(binary)         => operation("binary_division", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BinaryFloorDivision(binary)    => operation("binary_floor_division", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BinaryRemainder(binary)        => operation("binary_remainder", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::LogicalAnd(binary)             => operation("logical_and", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::LogicalOr(binary)              => operation("logical_or", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::LogicalXor(binary)             => operation("logical_xor", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BitwiseAnd(binary)             => operation("bitwise_and", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BitwiseOr(binary)              => operation("bitwise_or", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BitwiseXor(binary)             => operation("bitwise_xor", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BitwiseLeftShift(binary)       => operation("bitwise_left_shift", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::BitwiseRightShift(binary)      => operation("bitwise_right_shift", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::Call(value)                    => call(value),
+        Expression::Index(value)                   => index(value),
+        Expression::MemberAccess(value)            => member("member_access", value),
+        Expression::OptionalMember(value)          => member("optional_member", value),
+        Expression::NullCoalescing(binary)         => operation("null_coalescing", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::Equals(binary)                 => operation("equals", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::NotEquals(binary)              => operation("not_equals", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::LessThan(binary)               => operation("less_than", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::LessThanOrEquals(binary)       => operation("less_than_or_equals", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::GreaterThan(binary)            => operation("greater_than", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::GreaterThanOrEquals(binary)    => operation("greater_than_or_equals", vec![&binary.left_operand, &binary.right_operand]),
+        Expression::Block(value)                   => block(value),
+        Expression::StructLiteral(value)           => struct_literal(value),
+    })
+}
+
+fn statement(statement: &Statement) -> Json {
+    into_node(match statement {
+        Statement::Let(value)       => declaration(value),
+        Statement::Assign(value)    => operation("assign", vec![&value.target, &value.value]),
+        Statement::For(value)       => for_loop(value),
+        Statement::Struct(value)    => struct_declaration(value),
+        Statement::Enum(value)      => enum_declaration(value),
+        Statement::Function(value)  => function(value),
+        Statement::Expression(value) => expression_statement(value),
+    })
+}
+
+fn document(ast: &[Statement]) -> Json {
+    Json::Object(vec![
+        ("version", Json::Number(SCHEMA_VERSION)),
+        ("ast", Json::Array(ast.iter().map(statement).collect())),
+    ])
+}
+
+fn render(document: Json) -> String {
+    let mut output = String::new();
+    document.write(&mut output, 0);
+    output.push('\n');
+    output
+}
+
+/// Renders the statements of a script as a versioned JSON document,
+/// followed by a newline.
+pub fn ast_to_json(ast: &[Statement]) -> String {
+    render(document(ast))
+}
+
+/// Like [`ast_to_json`], with the `"span"` of every node, as returned by
+/// `parse_script_with_spans`.
+pub fn ast_to_json_with_spans(ast: &[Statement], spans: &[Range<usize>]) -> String {
+    let mut document = document(ast);
+    let mut spans = spans.iter();
+    document.add_spans(&mut spans);
+    assert!(spans.next().is_none(), "every span belongs to a node");
+    render(document)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ImportError {
+    /// The input is not well-formed JSON; holds the offending byte offset.
+    Syntax(usize),
+    UnsupportedVersion(u32),
+    /// The input is JSON, but not an AST of this schema.
+    InvalidNode(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Syntax(offset) =>
+                write!(f, "malformed JSON at byte {}", offset),
+            ImportError::UnsupportedVersion(version) =>
+                write!(f, "unsupported schema version {}, expected {}", version, SCHEMA_VERSION),
+            ImportError::InvalidNode(reason) =>
+                write!(f, "invalid AST: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+fn invalid(reason: impl Into<String>) -> ImportError {
+    ImportError::InvalidNode(reason.into())
+}
+
+/// A parsed JSON value. Numbers are limited to what the schema uses:
+/// non-negative integers that fit in a `u32`.
+enum Value {
+    Null,
+    Boolean(bool),
+    Number(u32),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(self: &Self) -> ImportError {
+        ImportError::Syntax(self.offset)
+    }
+
+    fn peek(self: &Self) -> Option<u8> {
+        self.input.get(self.offset).copied()
+    }
+
+    fn skip_whitespace(self: &mut Self) {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(self: &mut Self, byte: u8) -> Result<(), ImportError> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return Err(self.error());
+        }
+        self.offset += 1;
+        Ok(())
+    }
+
+    fn read_value(self: &mut Self) -> Result<Value, ImportError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.read_object(),
+            Some(b'[') => self.read_array(),
+            Some(b'"') => Ok(Value::String(self.read_string()?)),
+            Some(b't') => self.read_literal(b"true", Value::Boolean(true)),
+            Some(b'f') => self.read_literal(b"false", Value::Boolean(false)),
+            Some(b'n') => self.read_literal(b"null", Value::Null),
+            Some(b'0'..=b'9' | b'-') => self.read_number(),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn read_literal(self: &mut Self, literal: &[u8], value: Value) -> Result<Value, ImportError> {
+        if !self.input[self.offset..].starts_with(literal) {
+            return Err(self.error());
+        }
+        self.offset += literal.len();
+        Ok(value)
+    }
+
+    fn read_number(self: &mut Self) -> Result<Value, ImportError> {
+        let start = self.offset;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.peek() {
+            self.offset += 1;
+        }
+        let text = &self.input[start..self.offset];
+        if text.len() > 1 && text[0] == b'0' {
+            return Err(ImportError::Syntax(start));
+        }
+        if !text.iter().all(u8::is_ascii_digit) {
+            return Err(invalid("numbers must be non-negative integers"));
+        }
+        std::str::from_utf8(text).unwrap().parse()
+            .map(Value::Number)
+            .map_err(|_| invalid("number out of range"))
+    }
+
+    fn read_hexadecimal(self: &mut Self) -> Result<u32, ImportError> {
+        let digits = self.input.get(self.offset..self.offset + 4).ok_or(self.error())?;
+        let digits = std::str::from_utf8(digits).map_err(|_| self.error())?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| self.error())?;
+        self.offset += 4;
+        Ok(value)
+    }
+
+    fn read_string(self: &mut Self) -> Result<String, ImportError> {
+        self.expect(b'"')?;
+        let mut string = vec![];
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error());
+            };
+            match byte {
+                b'"' => {
+                    self.offset += 1;
+                    break;
+                },
+                b'\\' => {
+                    self.offset += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"')  => b'"',
+                        Some(b'\\') => b'\\',
+                        Some(b'/')  => b'/',
+                        Some(b'b')  => 0x08,
+                        Some(b'f')  => 0x0c,
+                        Some(b'n')  => b'\n',
+                        Some(b'r')  => b'\r',
+                        Some(b't')  => b'\t',
+                        Some(b'u') => {
+                            self.offset += 1;
+                            let character = self.read_code_point()?;
+                            let mut buffer = [0; 4];
+                            string.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                            continue;
+                        },
+                        _ => return Err(self.error()),
+                    };
+                    string.push(escaped);
+                    self.offset += 1;
+                },
+                0x00..=0x1f => {
+                    return Err(self.error());
+                },
+                _ => {
+                    string.push(byte);
+                    self.offset += 1;
+                },
+            }
+        }
+        // The input is a `str` and escapes are encoded as UTF-8.
+        Ok(String::from_utf8(string).unwrap())
+    }
+
+    /// Reads the digits of a `\u` escape, and of the low surrogate following
+    /// a high one.
+    fn read_code_point(self: &mut Self) -> Result<char, ImportError> {
+        let start = self.offset;
+        let mut code = self.read_hexadecimal()?;
+        if (0xd800..0xdc00).contains(&code) {
+            if !self.input[self.offset..].starts_with(b"\\u") {
+                return Err(ImportError::Syntax(start));
+            }
+            self.offset += 2;
+            let low = self.read_hexadecimal()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(ImportError::Syntax(start));
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        char::from_u32(code).ok_or(ImportError::Syntax(start))
+    }
+
+    fn read_array(self: &mut Self) -> Result<Value, ImportError> {
+        self.expect(b'[')?;
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.read_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Value::Array(items));
+                },
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn read_object(self: &mut Self) -> Result<Value, ImportError> {
+        self.expect(b'{')?;
+        let mut fields = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.read_string()?;
+            self.expect(b':')?;
+            fields.push((name, self.read_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(Value::Object(fields));
+                },
+                _ => return Err(self.error()),
+            }
+        }
+    }
+}
+
+const UNARY_KINDS: &[(&str, UnaryConstructor)] = &[
+    ("unary_addition", Expression::UnaryAddition),
+    ("unary_subtraction", Expression::UnarySubtraction),
+    ("logical_not", Expression::LogicalNot),
+    ("bitwise_not", Expression::BitwiseNot),
+];
+
+const BINARY_KINDS: &[(&str, BinaryConstructor)] = &[
+    ("binary_addition", Expression::BinaryAddition),
+    ("binary_subtraction", Expression::BinarySubtraction),
+    ("binary_multiplication", Expression::BinaryMultiplication),
+    ("binary_division", Expression::BinaryDivision),
+    ("binary_floor_division", Expression::BinaryFloorDivision),
+    ("binary_remainder", Expression::BinaryRemainder),
+    ("logical_and", Expression::LogicalAnd),
+    ("logical_or", Expression::LogicalOr),
+    ("logical_xor", Expression::LogicalXor),
+    ("bitwise_and", Expression::BitwiseAnd),
+    ("bitwise_or", Expression::BitwiseOr),
+    ("bitwise_xor", Expression::BitwiseXor),
+    ("bitwise_left_shift", Expression::BitwiseLeftShift),
+    ("bitwise_right_shift", Expression::BitwiseRightShift),
+    ("null_coalescing", Expression::NullCoalescing),
+    ("equals", Expression::Equals),
+    ("not_equals", Expression::NotEquals),
+    ("less_than", Expression::LessThan),
+    ("less_than_or_equals", Expression::LessThanOrEquals),
+    ("greater_than", Expression::GreaterThan),
+    ("greater_than_or_equals", Expression::GreaterThanOrEquals),
+];
+
+/// Takes the field `name` out of a node object.
+fn field(fields: &mut [(String, Value)], name: &str) -> Result<Value, ImportError> {
+    fields.iter_mut()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| std::mem::replace(value, Value::Null))
+        .ok_or_else(|| invalid(format!("missing field `{}`", name)))
+}
+
+fn optional_field(fields: &mut [(String, Value)], name: &str) -> Option<Value> {
+    field(fields, name).ok()
+}
+
+fn string_field(fields: &mut [(String, Value)], name: &str) -> Result<String, ImportError> {
+    match field(fields, name)? {
+        Value::String(string) => Ok(string),
+        _ => Err(invalid(format!("`{}` must be a string", name))),
+    }
+}
+
+fn array_field(fields: &mut [(String, Value)], name: &str) -> Result<Vec<Value>, ImportError> {
+    match field(fields, name)? {
+        Value::Array(items) => Ok(items),
+        _ => Err(invalid(format!("`{}` must be an array", name))),
+    }
+}
+
+fn string_array_field(fields: &mut [(String, Value)], name: &str) -> Result<Vec<String>, ImportError> {
+    array_field(fields, name)?.into_iter()
+        .map(|value| match value {
+            Value::String(value) => Ok(value),
+            _ => Err(invalid(format!("`{}` must hold strings", name))),
+        })
+        .collect()
+}
+
+fn children(fields: &mut [(String, Value)], count: usize) -> Result<Vec<Expression>, ImportError> {
+    let children = array_field(fields, "children")?;
+    if children.len() != count {
+        return Err(invalid(format!("expected {} children, found {}", count, children.len())));
+    }
+    children.into_iter().map(to_node).collect()
+}
+
+fn to_digits(digits: &str, radix: u32) -> Result<Vec<u8>, ImportError> {
+    digits.chars()
+        .map(|digit| digit.to_digit(radix).map(|digit| digit as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid(format!("`{}` is not a base {} number", digits, radix)))
+}
+
+fn to_integer(fields: &mut [(String, Value)]) -> Result<Expression, ImportError> {
+    let base = string_field(fields, "base")?;
+    let digits = string_field(fields, "digits")?;
+    let (radix, constructor): (u32, fn(Vec<u8>) -> IntegerRepresentation) = match base.as_str() {
+        "decimal"       => (10, IntegerRepresentation::Decimal),
+        "hexadecimal"   => (16, IntegerRepresentation::Hexadecimal),
+        "octal"         => (8, IntegerRepresentation::Octal),
+        "binary"        => (2, IntegerRepresentation::Binary),
+        _ => return Err(invalid(format!("unknown base `{}`", base))),
+    };
+    if digits.len() == 0 {
+        return Err(invalid("integer literal without digits"));
+    }
+    Ok(Expression::IntegerLiteral(Box::new(constructor(to_digits(&digits, radix)?))))
+}
+
+fn to_float(fields: &mut [(String, Value)]) -> Result<Expression, ImportError> {
+    let integer = to_digits(&string_field(fields, "integer")?, 10)?;
+    let fractional = to_digits(&string_field(fields, "fractional")?, 10)?;
+    if integer.len() == 0 && fractional.len() == 0 {
+        return Err(invalid("float literal without digits"));
+    }
+    let float = match optional_field(fields, "exponent") {
+        None => FloatRepresentation::Decimal { integer, fractional },
+        Some(Value::String(exponent)) => {
+            let (exponent_sign, exponent) = match exponent.strip_prefix('-') {
+                Some(exponent) => (Sign::Negative, exponent),
+                None => (Sign::Positive, exponent.as_str()),
+            };
+            let exponent = to_digits(exponent, 10)?;
+            if exponent.len() == 0 {
+                return Err(invalid("exponent without digits"));
+            }
+            FloatRepresentation::Scientific { integer, fractional, exponent_sign, exponent }
+        },
+        Some(_) => return Err(invalid("`exponent` must be a string")),
+    };
+    Ok(Expression::FloatLiteral(Box::new(float)))
+}
+
+fn to_string_literal(fields: &mut [(String, Value)]) -> Result<Expression, ImportError> {
+    if let Some(Value::String(value)) = optional_field(fields, "value") {
+        return Ok(ASTBuilder::string(value.as_bytes()));
+    }
+    let bytes = array_field(fields, "bytes")
+        .map_err(|_| invalid("`string_literal` needs a string `value` or a `bytes` array"))?;
+    let bytes = bytes.into_iter()
+        .map(|byte| match byte {
+            Value::Number(byte) => u8::try_from(byte).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| invalid("`bytes` must hold numbers from 0 to 255"))?;
+    Ok(ASTBuilder::string(&bytes))
+}
+
+fn to_node(value: Value) -> Result<Expression, ImportError> {
+    let Value::Object(mut fields) = value else {
+        return Err(invalid("expected a node object"));
+    };
+    let fields = fields.as_mut_slice();
+    let kind = string_field(fields, "kind")?;
+    let build_error = |error: crate::builder::BuildError| invalid(error.to_string());
+
+    match kind.as_str() {
+        "identifier" => {
+            ASTBuilder::identifier(&string_field(fields, "name")?).map_err(build_error)
+        },
+        "path" => {
+            let segments = string_array_field(fields, "segments")?;
+            if segments.len() < 2 {
+                return Err(invalid("a path needs at least two segments"));
+            }
+            let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+            ASTBuilder::path(&segments).map_err(build_error)
+        },
+        "integer_literal" => to_integer(fields),
+        "float_literal" => to_float(fields),
+        "boolean_literal" => {
+            let Value::Boolean(value) = field(fields, "value")? else {
+                return Err(invalid("`value` must be a boolean"));
+            };
+            Ok(ASTBuilder::boolean(value))
+        },
+        "character_literal" => {
+            let value = string_field(fields, "value")?;
+            let mut characters = value.chars();
+            match (characters.next(), characters.next()) {
+                (Some(character), None) => Ok(ASTBuilder::character(character)),
+                _ => Err(invalid("a character literal holds exactly one character")),
+            }
+        },
+        "string_literal" => to_string_literal(fields),
+        "interpolated_string" => {
+            let parts = array_field(fields, "children")?.into_iter()
+                .map(to_node)
+                .collect::<Result<_, _>>()?;
+            Ok(ASTBuilder::interpolated_string(parts))
+        },
+        "call" => {
+            let callee = to_node(field(fields, "callee")?)?;
+            let arguments = array_field(fields, "arguments")?.into_iter()
+                .map(to_node)
+                .collect::<Result<_, _>>()?;
+            Ok(ASTBuilder::call(callee, arguments))
+        },
+        "array_literal" => {
+            let elements = array_field(fields, "elements")?.into_iter()
+                .map(to_node)
+                .collect::<Result<_, _>>()?;
+            Ok(ASTBuilder::array(elements))
+        },
+        "conditional" => {
+            let condition = to_node(field(fields, "condition")?)?;
+            let consequent = to_node(field(fields, "consequent")?)?;
+            let alternative = to_node(field(fields, "alternative")?)?;
+            Ok(ASTBuilder::conditional(condition, consequent, alternative))
+        },
+        "map_literal" => {
+            let mut entries = vec![];
+            for entry in array_field(fields, "entries")? {
+                let Value::Object(mut entry) = entry else {
+                    return Err(invalid("`entries` must hold objects"));
+                };
+                entries.push((to_node(field(&mut entry, "key")?)?, to_node(field(&mut entry, "value")?)?));
+            }
+            ASTBuilder::map(entries).map_err(build_error)
+        },
+        "tuple_literal" => {
+            let elements = array_field(fields, "elements")?.into_iter()
+                .map(to_node)
+                .collect::<Result<_, _>>()?;
+            Ok(ASTBuilder::tuple(elements))
+        },
+        "index" => {
+            let object = to_node(field(fields, "object")?)?;
+            let index = to_node(field(fields, "index")?)?;
+            Ok(ASTBuilder::index(object, index))
+        },
+        "member_access" => {
+            let object = to_node(field(fields, "object")?)?;
+            ASTBuilder::member_access(object, &string_field(fields, "name")?).map_err(build_error)
+        },
+        "optional_member" => {
+            let object = to_node(field(fields, "object")?)?;
+            ASTBuilder::optional_member(object, &string_field(fields, "name")?).map_err(build_error)
+        },
+        "struct_literal" => {
+            let segments = string_array_field(fields, "segments")?;
+            let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+            let mut names = vec![];
+            let mut values = vec![];
+            for value in array_field(fields, "fields")? {
+                let Value::Object(mut value) = value else {
+                    return Err(invalid("`fields` must hold objects"));
+                };
+                names.push(string_field(&mut value, "name")?);
+                values.push(to_node(field(&mut value, "value")?)?);
+            }
+            let fields = names.iter().map(String::as_str).zip(values).collect();
+            ASTBuilder::struct_literal(&segments, fields).map_err(build_error)
+        },
+        "block" => {
+            let statements = array_field(fields, "statements")?.into_iter()
+                .map(to_statement)
+                .collect::<Result<_, _>>()?;
+            let value = optional_field(fields, "value").map(to_node).transpose()?;
+            Ok(ASTBuilder::block(statements, value))
+        },
+        kind => {
+            if let Some((_, constructor)) = UNARY_KINDS.iter().find(|(name, _)| *name == kind) {
+                let operand = children(fields, 1)?.pop().unwrap();
+                return Ok(constructor(Box::new(UnaryOperation { operand })));
+            }
+            if let Some((_, constructor)) = BINARY_KINDS.iter().find(|(name, _)| *name == kind) {
+                let mut children = children(fields, 2)?;
+                let right_operand = children.pop().unwrap();
+                let left_operand = children.pop().unwrap();
+                return Ok(constructor(Box::new(BinaryOperation { left_operand, right_operand })));
+            }
+            Err(invalid(format!("unknown node kind `{}`", kind)))
+        },
+    }
+}
+
+fn to_type(value: Value) -> Result<TypeExpr, ImportError> {
+    let Value::Object(mut fields) = value else {
+        return Err(invalid("expected a type object"));
+    };
+    let fields = fields.as_mut_slice();
+    match string_field(fields, "kind")?.as_str() {
+        "named_type" => {
+            let segments = string_array_field(fields, "segments")?;
+            let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+            ASTBuilder::named_type(&segments).map_err(|error| invalid(error.to_string()))
+        },
+        "array_type" => Ok(ASTBuilder::array_type(to_type(field(fields, "element")?)?)),
+        "tuple_type" => {
+            let elements = array_field(fields, "elements")?.into_iter()
+                .map(to_type)
+                .collect::<Result<_, _>>()?;
+            Ok(ASTBuilder::tuple_type(elements))
+        },
+        kind => Err(invalid(format!("unknown type kind `{}`", kind))),
+    }
+}
+
+fn to_pattern(value: Value) -> Result<Pattern, ImportError> {
+    let Value::Object(mut fields) = value else {
+        return Err(invalid("expected a pattern object"));
+    };
+    let fields = fields.as_mut_slice();
+    match string_field(fields, "kind")?.as_str() {
+        "identifier_pattern" => {
+            ASTBuilder::identifier_pattern(&string_field(fields, "name")?).map_err(|error| invalid(error.to_string()))
+        },
+        "tuple_pattern" => {
+            let elements = array_field(fields, "elements")?.into_iter()
+                .map(to_pattern)
+                .collect::<Result<_, _>>()?;
+            Ok(ASTBuilder::tuple_pattern(elements))
+        },
+        kind => Err(invalid(format!("unknown pattern kind `{}`", kind))),
+    }
+}
+
+fn to_statement(value: Value) -> Result<Statement, ImportError> {
+    let Value::Object(mut fields) = value else {
+        return Err(invalid("expected a statement object"));
+    };
+    let fields = fields.as_mut_slice();
+    let kind = string_field(fields, "kind")?;
+
+    match kind.as_str() {
+        "let" => {
+            let pattern = match optional_field(fields, "pattern") {
+                Some(pattern) => to_pattern(pattern)?,
+                None => ASTBuilder::identifier_pattern(&string_field(fields, "name")?)
+                    .map_err(|error| invalid(error.to_string()))?,
+            };
+            let Value::Boolean(mutable) = field(fields, "mutable")? else {
+                return Err(invalid("`mutable` must be a boolean"));
+            };
+            let value = to_node(field(fields, "value")?)?;
+            let annotation = optional_field(fields, "type").map(to_type).transpose()?;
+            Ok(ASTBuilder::destructure(pattern, mutable, annotation, value))
+        },
+        "assign" => {
+            let mut children = children(fields, 2)?;
+            let value = children.pop().unwrap();
+            let target = children.pop().unwrap();
+            if !matches!(target, Expression::Identifier(_) | Expression::Index(_) | Expression::MemberAccess(_)) {
+                return Err(invalid("only variables, indexing and member access can be assigned to"));
+            }
+            Ok(Statement::Assign(Box::new(Assignment { target, value })))
+        },
+        "expression" => {
+            Ok(ASTBuilder::expression_statement(to_node(field(fields, "value")?)?))
+        },
+        "for" => {
+            let binding = string_field(fields, "binding")?;
+            let iterable = to_node(field(fields, "iterable")?)?;
+            let Expression::Block(body) = to_node(field(fields, "body")?)? else {
+                return Err(invalid("`body` must be a block"));
+            };
+            ASTBuilder::for_loop(&binding, iterable, *body).map_err(|error| invalid(error.to_string()))
+        },
+        "struct" => {
+            let name = string_field(fields, "name")?;
+            let names = string_array_field(fields, "fields")?;
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            ASTBuilder::struct_declaration(&name, &names).map_err(|error| invalid(error.to_string()))
+        },
+        "function" => {
+            let name = string_field(fields, "name")?;
+            let mut names = vec![];
+            let mut annotations = vec![];
+            for parameter in array_field(fields, "parameters")? {
+                let Value::Object(mut parameter) = parameter else {
+                    return Err(invalid("`parameters` must hold objects"));
+                };
+                names.push(string_field(&mut parameter, "name")?);
+                annotations.push(optional_field(&mut parameter, "type").map(to_type).transpose()?);
+            }
+            let parameters = names.iter().map(String::as_str).zip(annotations).collect();
+            let return_type = optional_field(fields, "return_type").map(to_type).transpose()?;
+            let Expression::Block(body) = to_node(field(fields, "body")?)? else {
+                return Err(invalid("`body` must be a block"));
+            };
+            ASTBuilder::function(&name, parameters, return_type, *body).map_err(|error| invalid(error.to_string()))
+        },
+        "enum" => {
+            let name = string_field(fields, "name")?;
+            let mut variants = vec![];
+            for variant in array_field(fields, "variants")? {
+                let Value::Object(mut variant) = variant else {
+                    return Err(invalid("`variants` must hold objects"));
+                };
+                variants.push((string_field(&mut variant, "name")?, string_array_field(&mut variant, "fields")?));
+            }
+            let fields: Vec<Vec<&str>> = variants.iter()
+                .map(|(_, fields)| fields.iter().map(String::as_str).collect())
+                .collect();
+            let variants: Vec<(&str, &[&str])> = variants.iter()
+                .zip(fields.iter())
+                .map(|((name, _), fields)| (name.as_str(), fields.as_slice()))
+                .collect();
+            ASTBuilder::enum_declaration(&name, &variants).map_err(|error| invalid(error.to_string()))
+        },
+        kind => Err(invalid(format!("unknown statement kind `{}`", kind))),
+    }
+}
+
+/// Reads an AST back from a document written by [`ast_to_json`].
+pub fn ast_from_json(json: &str) -> Result<Vec<Statement>, ImportError> {
+    let mut reader = Reader { input: json.as_bytes(), offset: 0 };
+    let document = reader.read_value()?;
+    reader.skip_whitespace();
+    if reader.offset != reader.input.len() {
+        return Err(reader.error());
+    }
+
+    let Value::Object(mut fields) = document else {
+        return Err(invalid("expected a document object"));
+    };
+    match field(&mut fields, "version")? {
+        Value::Number(SCHEMA_VERSION) => {},
+        Value::Number(version) => return Err(ImportError::UnsupportedVersion(version)),
+        _ => return Err(invalid("`version` must be a number")),
+    }
+    array_field(&mut fields, "ast")?.into_iter().map(to_statement).collect()
+}
@@ -1,6 +1,16 @@
+use std::collections::VecDeque;
+use std::fmt;
 use std::mem::take;
+use std::ops::Range;
+use unicode_width::UnicodeWidthStr;
 
+#[derive(Clone, Copy)]
 enum State {
+    ScriptStart,
+    ByteOrderMark,
+    ByteOrderMarkEnd,
+    Hash,
+    Shebang,
     Start,
     Identifier,
     Zero,
@@ -11,8 +21,28 @@ enum State {
     Binary,
     Fractional,
     Exponent,
+    ExponentSign,
     Equals,
+    Plus,
     Minus,
+    Asterisk,
+    ForwardSlash,
+    DoubleForwardSlash,
+    Percent,
+    Colon,
+    VerticalBar,
+    LessThan,
+    GreaterThan,
+    Exclamation,
+    Question,
+    Character,
+    String,
+    Escape,
+    HexadecimalEscape,
+    UnicodeEscape,
+    UnicodeEscapeDigits,
+    StringDollar,
+    Interpolation,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -23,6 +53,13 @@ pub enum IntegerRepresentation {
     Binary(Vec<u8>),
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sign {
+    #[default]
+    Positive,
+    Negative,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum FloatRepresentation {
     Decimal {
@@ -32,23 +69,116 @@ pub enum FloatRepresentation {
     Scientific {
         integer: Vec<u8>,
         fractional: Vec<u8>,
+        exponent_sign: Sign,
         exponent: Vec<u8>,
     },
 }
 
+impl IntegerRepresentation {
+    /// The base of the literal and its digit values, most significant first.
+    pub fn digits(self: &Self) -> (u32, &[u8]) {
+        match self {
+            IntegerRepresentation::Decimal(digits)      => (10, digits),
+            IntegerRepresentation::Hexadecimal(digits)  => (16, digits),
+            IntegerRepresentation::Octal(digits)        => (8, digits),
+            IntegerRepresentation::Binary(digits)       => (2, digits),
+        }
+    }
+
+    /// The value of the literal, or `None` if it doesn't fit.
+    pub fn to_u64(self: &Self) -> Option<u64> {
+        let (radix, digits) = self.digits();
+        digits.iter().try_fold(0u64, |value, &digit| {
+            value.checked_mul(radix as u64)?.checked_add(digit as u64)
+        })
+    }
+
+    /// The value of the literal, or `None` if it doesn't fit. Literals are
+    /// unsigned, so the result is never negative.
+    pub fn to_i128(self: &Self) -> Option<i128> {
+        let (radix, digits) = self.digits();
+        digits.iter().try_fold(0i128, |value, &digit| {
+            value.checked_mul(radix as i128)?.checked_add(digit as i128)
+        })
+    }
+
+    /// The value of the literal, however large.
+    #[cfg(feature = "bigint")]
+    pub fn to_bigint(self: &Self) -> num_bigint::BigInt {
+        let (radix, digits) = self.digits();
+        num_bigint::BigUint::from_radix_be(digits, radix)
+            .expect("the lexer only produces digits of the literal's base")
+            .into()
+    }
+}
+
+impl FloatRepresentation {
+    /// The `f64` nearest to the literal, or `None` if it is too large to be
+    /// finite.
+    pub fn to_f64(self: &Self) -> Option<f64> {
+        let (integer, fractional, exponent_sign, exponent) = match self {
+            FloatRepresentation::Decimal { integer, fractional } =>
+                (integer, fractional, Sign::Positive, &[][..]),
+            FloatRepresentation::Scientific { integer, fractional, exponent_sign, exponent } =>
+                (integer, fractional, *exponent_sign, exponent.as_slice()),
+        };
+        let decimal = |digits: &[u8]| -> String {
+            digits.iter().map(|&digit| char::from(b'0' + digit)).collect()
+        };
+        let mut text = format!("0{}.{}0", decimal(integer), decimal(fractional));
+        if exponent.len() != 0 {
+            let sign = if exponent_sign == Sign::Negative { "-" } else { "" };
+            text.push_str(&format!("e{}{}", sign, decimal(exponent)));
+        }
+        let value: f64 = text.parse().ok()?;
+        value.is_finite().then_some(value)
+    }
+}
+
+/// A piece of an interpolated string: literal text, or the tokens of an
+/// embedded `${...}` expression.
+#[derive(Debug, PartialEq)]
+pub enum StringSegment {
+    Literal(Vec<u8>),
+    Tokens(Vec<Token>),
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Token {
     Plus,
     Minus,
     Asterisk,
     ForwardSlash,
+    DoubleForwardSlash,
+    Percent,
+    Ampersand,
+    VerticalBar,
+    Caret,
+    Tilde,
     Dot,
+    QuestionDot,
     Comma,
     Colon,
+    DoubleColon,
     Semicolon,
     Assign,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    ForwardSlashAssign,
+    DoubleForwardSlashAssign,
+    PercentAssign,
     Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+    LeftShift,
+    RightShift,
     RightArrow,
+    Pipeline,
+    DoubleQuestion,
     LeftParenthesis,
     RightParenthesis,
     LeftBracket,
@@ -65,24 +195,346 @@ pub enum Token {
     Else,
     Function,
     If,
+    Then,
     Let,
+    Mut,
     Return,
     Lambda,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Match,
+    Struct,
+    Enum,
+    Const,
+    Import,
 
     Identifier(Box<Vec<u8>>),
+    Extension(Box<Vec<u8>>),
     Integer(Box<IntegerRepresentation>),
     Float(Box<FloatRepresentation>),
+    Character(char),
+    String(Box<Vec<u8>>),
+    InterpolatedString(Box<Vec<StringSegment>>),
+
+    EOF,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Plus,
+    Minus,
+    Asterisk,
+    ForwardSlash,
+    DoubleForwardSlash,
+    Percent,
+    Ampersand,
+    VerticalBar,
+    Caret,
+    Tilde,
+    Dot,
+    QuestionDot,
+    Comma,
+    Colon,
+    DoubleColon,
+    Semicolon,
+    Assign,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    ForwardSlashAssign,
+    DoubleForwardSlashAssign,
+    PercentAssign,
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+    LeftShift,
+    RightShift,
+    RightArrow,
+    Pipeline,
+    DoubleQuestion,
+    LeftParenthesis,
+    RightParenthesis,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+
+    False,
+    True,
+    And,
+    Or,
+    Not,
+    Xor,
+    Else,
+    Function,
+    If,
+    Then,
+    Let,
+    Mut,
+    Return,
+    Lambda,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Match,
+    Struct,
+    Enum,
+    Const,
+    Import,
+
+    Identifier,
+    Extension,
+    Integer,
+    Float,
+    Character,
+    String,
+    InterpolatedString,
 
     EOF,
 }
 
+impl TokenKind {
+    /// The source text of a token of this kind, or `None` if tokens of this
+    /// kind carry a payload.
+    pub fn spelling(self: &Self) -> Option<&'static str> {
+        let text = match self {
+            TokenKind::Plus                 => "+",
+            TokenKind::Minus                => "-",
+            TokenKind::Asterisk             => "*",
+            TokenKind::ForwardSlash         => "/",
+            TokenKind::DoubleForwardSlash   => "//",
+            TokenKind::Percent              => "%",
+            TokenKind::Ampersand            => "&",
+            TokenKind::VerticalBar          => "|",
+            TokenKind::Caret                => "^",
+            TokenKind::Tilde                => "~",
+            TokenKind::Dot                  => ".",
+            TokenKind::QuestionDot          => "?.",
+            TokenKind::Comma                => ",",
+            TokenKind::Colon                => ":",
+            TokenKind::DoubleColon          => "::",
+            TokenKind::Semicolon            => ";",
+            TokenKind::Assign               => "=",
+            TokenKind::PlusAssign           => "+=",
+            TokenKind::MinusAssign          => "-=",
+            TokenKind::AsteriskAssign       => "*=",
+            TokenKind::ForwardSlashAssign   => "/=",
+            TokenKind::DoubleForwardSlashAssign => "//=",
+            TokenKind::PercentAssign        => "%=",
+            TokenKind::Equals               => "==",
+            TokenKind::NotEquals            => "!=",
+            TokenKind::LessThan             => "<",
+            TokenKind::LessThanOrEquals     => "<=",
+            TokenKind::GreaterThan          => ">",
+            TokenKind::GreaterThanOrEquals  => ">=",
+            TokenKind::LeftShift            => "<<",
+            TokenKind::RightShift           => ">>",
+            TokenKind::RightArrow           => "->",
+            TokenKind::Pipeline             => "|>",
+            TokenKind::DoubleQuestion       => "??",
+            TokenKind::LeftParenthesis      => "(",
+            TokenKind::RightParenthesis     => ")",
+            TokenKind::LeftBracket          => "[",
+            TokenKind::RightBracket         => "]",
+            TokenKind::LeftBrace            => "{",
+            TokenKind::RightBrace           => "}",
+            TokenKind::False                => "false",
+            TokenKind::True                 => "true",
+            TokenKind::And                  => "and",
+            TokenKind::Or                   => "or",
+            TokenKind::Not                  => "not",
+            TokenKind::Xor                  => "xor",
+            TokenKind::Else                 => "else",
+            TokenKind::Function             => "function",
+            TokenKind::If                   => "if",
+            TokenKind::Then                 => "then",
+            TokenKind::Let                  => "let",
+            TokenKind::Mut                  => "mut",
+            TokenKind::Return               => "return",
+            TokenKind::Lambda               => "lambda",
+            TokenKind::While                => "while",
+            TokenKind::For                  => "for",
+            TokenKind::In                   => "in",
+            TokenKind::Break                => "break",
+            TokenKind::Continue             => "continue",
+            TokenKind::Match                => "match",
+            TokenKind::Struct               => "struct",
+            TokenKind::Enum                 => "enum",
+            TokenKind::Const                => "const",
+            TokenKind::Import               => "import",
+            _ => return None,
+        };
+        Some(text)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(spelling) = self.spelling() {
+            return write!(f, "`{}`", spelling);
+        }
+        let text = match self {
+            TokenKind::Identifier           => "identifier",
+            TokenKind::Extension            => "extension keyword",
+            TokenKind::Integer              => "integer literal",
+            TokenKind::Float                => "float literal",
+            TokenKind::Character            => "character literal",
+            TokenKind::String               => "string literal",
+            TokenKind::InterpolatedString   => "interpolated string",
+            TokenKind::EOF                  => "end of input",
+            _ => unreachable!(),
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl Token {
+    pub fn kind(self: &Self) -> TokenKind {
+        match self {
+            Token::Plus                 => TokenKind::Plus,
+            Token::Minus                => TokenKind::Minus,
+            Token::Asterisk             => TokenKind::Asterisk,
+            Token::ForwardSlash         => TokenKind::ForwardSlash,
+            Token::DoubleForwardSlash   => TokenKind::DoubleForwardSlash,
+            Token::Percent              => TokenKind::Percent,
+            Token::Ampersand            => TokenKind::Ampersand,
+            Token::VerticalBar          => TokenKind::VerticalBar,
+            Token::Caret                => TokenKind::Caret,
+            Token::Tilde                => TokenKind::Tilde,
+            Token::Dot                  => TokenKind::Dot,
+            Token::QuestionDot          => TokenKind::QuestionDot,
+            Token::Comma                => TokenKind::Comma,
+            Token::Colon                => TokenKind::Colon,
+            Token::DoubleColon          => TokenKind::DoubleColon,
+            Token::Semicolon            => TokenKind::Semicolon,
+            Token::Assign               => TokenKind::Assign,
+            Token::PlusAssign           => TokenKind::PlusAssign,
+            Token::MinusAssign          => TokenKind::MinusAssign,
+            Token::AsteriskAssign       => TokenKind::AsteriskAssign,
+            Token::ForwardSlashAssign   => TokenKind::ForwardSlashAssign,
+            Token::DoubleForwardSlashAssign => TokenKind::DoubleForwardSlashAssign,
+            Token::PercentAssign        => TokenKind::PercentAssign,
+            Token::Equals               => TokenKind::Equals,
+            Token::NotEquals            => TokenKind::NotEquals,
+            Token::LessThan             => TokenKind::LessThan,
+            Token::LessThanOrEquals     => TokenKind::LessThanOrEquals,
+            Token::GreaterThan          => TokenKind::GreaterThan,
+            Token::GreaterThanOrEquals  => TokenKind::GreaterThanOrEquals,
+            Token::LeftShift            => TokenKind::LeftShift,
+            Token::RightShift           => TokenKind::RightShift,
+            Token::RightArrow           => TokenKind::RightArrow,
+            Token::Pipeline             => TokenKind::Pipeline,
+            Token::DoubleQuestion       => TokenKind::DoubleQuestion,
+            Token::LeftParenthesis      => TokenKind::LeftParenthesis,
+            Token::RightParenthesis     => TokenKind::RightParenthesis,
+            Token::LeftBracket          => TokenKind::LeftBracket,
+            Token::RightBracket         => TokenKind::RightBracket,
+            Token::LeftBrace            => TokenKind::LeftBrace,
+            Token::RightBrace           => TokenKind::RightBrace,
+            Token::False                => TokenKind::False,
+            Token::True                 => TokenKind::True,
+            Token::And                  => TokenKind::And,
+            Token::Or                   => TokenKind::Or,
+            Token::Not                  => TokenKind::Not,
+            Token::Xor                  => TokenKind::Xor,
+            Token::Else                 => TokenKind::Else,
+            Token::Function             => TokenKind::Function,
+            Token::If                   => TokenKind::If,
+            Token::Then                 => TokenKind::Then,
+            Token::Let                  => TokenKind::Let,
+            Token::Mut                  => TokenKind::Mut,
+            Token::Return               => TokenKind::Return,
+            Token::Lambda               => TokenKind::Lambda,
+            Token::While                => TokenKind::While,
+            Token::For                  => TokenKind::For,
+            Token::In                   => TokenKind::In,
+            Token::Break                => TokenKind::Break,
+            Token::Continue             => TokenKind::Continue,
+            Token::Match                => TokenKind::Match,
+            Token::Struct               => TokenKind::Struct,
+            Token::Enum                 => TokenKind::Enum,
+            Token::Const                => TokenKind::Const,
+            Token::Import               => TokenKind::Import,
+            Token::Identifier(_)        => TokenKind::Identifier,
+            Token::Extension(_)         => TokenKind::Extension,
+            Token::Integer(_)           => TokenKind::Integer,
+            Token::Float(_)             => TokenKind::Float,
+            Token::Character(_)         => TokenKind::Character,
+            Token::String(_)            => TokenKind::String,
+            Token::InterpolatedString(_)=> TokenKind::InterpolatedString,
+            Token::EOF                  => TokenKind::EOF,
+        }
+    }
+}
+
+/// Syntax an embedder adds on top of the built-in grammar, or takes away
+/// from it.
+#[derive(Clone, Debug, Default)]
+pub struct LexerOptions {
+    keywords: Vec<Vec<u8>>,
+    disabled_keywords: Vec<Vec<u8>>,
+    allow_leading_zeros: bool,
+}
+
+impl LexerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves `keyword`, which is then lexed as `Token::Extension` instead
+    /// of an identifier. Built-in keywords keep their meaning unless
+    /// disabled.
+    pub fn keyword(self: &mut Self, keyword: &str) -> &mut Self {
+        self.keywords.push(keyword.as_bytes().to_vec());
+        self
+    }
+
+    /// Lexes the built-in keyword `keyword` as an identifier, e.g. so scripts
+    /// can name a variable `lambda`.
+    pub fn disable_keyword(self: &mut Self, keyword: &str) -> &mut Self {
+        self.disabled_keywords.push(keyword.as_bytes().to_vec());
+        self
+    }
+
+    /// Accepts zero-padded decimal literals such as `007`, keeping every
+    /// digit, instead of failing with `Error::LeadingZeroWithoutBase`.
+    pub fn allow_leading_zeros(self: &mut Self) -> &mut Self {
+        self.allow_leading_zeros = true;
+        self
+    }
+}
+
 struct Lexer {
     state: State,
     integer: Vec<u8>,
     fractional: Vec<u8>,
+    exponent_sign: Sign,
     exponent: Vec<u8>,
     identifier: Vec<u8>,
+    literal: Vec<u8>,
+    literal_state: State,
+    escape: Vec<u8>,
+    segments: Vec<StringSegment>,
+    interpolation: Option<Box<Lexer>>,
+    interpolation_depth: usize,
     tokens: Vec<Token>,
+    /// Whether tokens are emitted with their payloads into `tokens`, rather
+    /// than as bare kinds into `kinds`.
+    payloads: bool,
+    kinds: Vec<TokenKind>,
+    position: Position,
+    /// Where the byte order mark or `#!` line being matched starts, which
+    /// errors about it point at.
+    prefix_start: Position,
+    options: LexerOptions,
 }
 
 enum Action {
@@ -98,30 +550,257 @@ enum InternalError {
     InvalidOctalDigit,
     InvalidBinaryDigit,
     MissingDigitsAfterBasePrefix,
+    MissingDigitsAfterExponentMark,
+    InvalidCharacterLiteral,
+    UnterminatedCharacterLiteral,
+    UnterminatedStringLiteral,
+    InvalidEscapeSequence,
+    UnsupportedEncoding,
+}
+
+impl InternalError {
+    fn at(self: Self, position: Position) -> Error {
+        match self {
+            InternalError::UnexpectedByte                   => Error::UnexpectedByte(position),
+            InternalError::InvalidNumberDigit               => Error::InvalidNumberDigit(position),
+            InternalError::LeadingZeroWithoutBase           => Error::LeadingZeroWithoutBase(position),
+            InternalError::InvalidHexadecimalDigit          => Error::InvalidHexadecimalDigit(position),
+            InternalError::InvalidOctalDigit                => Error::InvalidOctalDigit(position),
+            InternalError::InvalidBinaryDigit               => Error::InvalidBinaryDigit(position),
+            InternalError::MissingDigitsAfterBasePrefix     => Error::MissingDigitsAfterBasePrefix(position),
+            InternalError::MissingDigitsAfterExponentMark   => Error::MissingDigitsAfterExponentMark(position),
+            InternalError::InvalidCharacterLiteral          => Error::InvalidCharacterLiteral(position),
+            InternalError::UnterminatedCharacterLiteral     => Error::UnterminatedCharacterLiteral(position),
+            InternalError::UnterminatedStringLiteral        => Error::UnterminatedStringLiteral(position),
+            InternalError::InvalidEscapeSequence            => Error::InvalidEscapeSequence(position),
+            InternalError::UnsupportedEncoding              => Error::UnsupportedEncoding(position),
+        }
+    }
+}
+
+/// Location of a byte in a script. `line` and `column` start at 1 and
+/// count bytes; `offset` starts at 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Position {
+    fn start() -> Self {
+        Self { line: 1, column: 1, offset: 0 }
+    }
+
+    /// The position of the byte at `offset` in `script`, such as the offset
+    /// of a parser error.
+    pub fn locate(script: &[u8], offset: usize) -> Self {
+        let mut position = Self::start();
+        for &byte in &script[..offset.min(script.len())] {
+            position.advance(byte);
+        }
+        position
+    }
+
+    fn advance(self: &mut Self, byte: u8) {
+        self.offset += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    /// The column at which this position shows up in a terminal, for lining
+    /// up carets under `script`, the script the position points into. Unlike
+    /// `column`, it counts characters: wide ones such as CJK and emoji take
+    /// two columns and combining marks none. Tabs count as one column.
+    pub fn display_column(self: &Self, script: &[u8]) -> usize {
+        let line = String::from_utf8_lossy(&script[self.offset + 1 - self.column..self.offset]);
+        let tabs = line.matches('\t').count();
+        line.split('\t').map(UnicodeWidthStr::width).sum::<usize>() + tabs + 1
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
 }
 
 #[derive(Debug)]
 pub enum Error {
-    UnexpectedByte(usize),
-    InvalidNumberDigit(usize),
-    LeadingZeroWithoutBase(usize),
-    InvalidHexadecimalDigit(usize),
-    InvalidOctalDigit(usize),
-    InvalidBinaryDigit(usize),
-    MissingDigitsAfterBasePrefix(usize),
-    MissingDigitsAfterExponentMark(usize),
+    UnexpectedByte(Position),
+    InvalidNumberDigit(Position),
+    LeadingZeroWithoutBase(Position),
+    InvalidHexadecimalDigit(Position),
+    InvalidOctalDigit(Position),
+    InvalidBinaryDigit(Position),
+    MissingDigitsAfterBasePrefix(Position),
+    MissingDigitsAfterExponentMark(Position),
+    InvalidCharacterLiteral(Position),
+    UnterminatedCharacterLiteral(Position),
+    UnterminatedStringLiteral(Position),
+    InvalidEscapeSequence(Position),
+    /// The script starts with a UTF-16 byte order mark.
+    UnsupportedEncoding(Position),
+}
+
+impl Error {
+    pub fn position(self: &Self) -> Position {
+        match self {
+            Error::UnexpectedByte(position) => *position,
+            Error::InvalidNumberDigit(position) => *position,
+            Error::LeadingZeroWithoutBase(position) => *position,
+            Error::InvalidHexadecimalDigit(position) => *position,
+            Error::InvalidOctalDigit(position) => *position,
+            Error::InvalidBinaryDigit(position) => *position,
+            Error::MissingDigitsAfterBasePrefix(position) => *position,
+            Error::MissingDigitsAfterExponentMark(position) => *position,
+            Error::InvalidCharacterLiteral(position) => *position,
+            Error::UnterminatedCharacterLiteral(position) => *position,
+            Error::UnterminatedStringLiteral(position) => *position,
+            Error::InvalidEscapeSequence(position) => *position,
+            Error::UnsupportedEncoding(position) => *position,
+        }
+    }
 }
 
+impl fmt::Display for Error {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnexpectedByte(position) =>
+                write!(f, "unexpected byte at {}", position),
+            Error::InvalidNumberDigit(position) =>
+                write!(f, "invalid digit in number literal at {}", position),
+            Error::LeadingZeroWithoutBase(position) =>
+                write!(f, "leading zero without base prefix at {}", position),
+            Error::InvalidHexadecimalDigit(position) =>
+                write!(f, "invalid hexadecimal digit at {}", position),
+            Error::InvalidOctalDigit(position) =>
+                write!(f, "invalid octal digit at {}", position),
+            Error::InvalidBinaryDigit(position) =>
+                write!(f, "invalid binary digit at {}", position),
+            Error::MissingDigitsAfterBasePrefix(position) =>
+                write!(f, "missing digits after base prefix at {}", position),
+            Error::MissingDigitsAfterExponentMark(position) =>
+                write!(f, "missing digits after exponent mark at {}", position),
+            Error::InvalidCharacterLiteral(position) =>
+                write!(f, "invalid character literal at {}", position),
+            Error::UnterminatedCharacterLiteral(position) =>
+                write!(f, "unterminated character literal at {}", position),
+            Error::UnterminatedStringLiteral(position) =>
+                write!(f, "unterminated string literal at {}", position),
+            Error::InvalidEscapeSequence(position) =>
+                write!(f, "invalid escape sequence at {}", position),
+            Error::UnsupportedEncoding(position) =>
+                write!(f, "unsupported encoding at {}, scripts must be UTF-8", position),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl Lexer {
     fn new() -> Self {
         Self {
-            state: State::Start,
+            state: State::ScriptStart,
             integer: vec![],
             fractional: vec![],
+            exponent_sign: Sign::Positive,
             exponent: vec![],
             identifier: vec![],
+            literal: vec![],
+            literal_state: State::Start,
+            escape: vec![],
+            segments: vec![],
+            interpolation: None,
+            interpolation_depth: 0,
             tokens: vec![],
+            payloads: true,
+            kinds: vec![],
+            position: Position::start(),
+            prefix_start: Position::start(),
+            options: LexerOptions::default(),
+        }
+    }
+
+    /// Skips a UTF-8 byte order mark and a `#!` line at the very beginning of
+    /// a script, so scripts can be made executable.
+    fn run_fsm_script_start(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            0xEF => {
+                self.prefix_start = self.position;
+                self.state = State::ByteOrderMark;
+                Ok(Action::Continue)
+            },
+            0xFE | 0xFF => {
+                Err(InternalError::UnsupportedEncoding)
+            },
+            b'#' => {
+                self.prefix_start = self.position;
+                self.state = State::Hash;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_byte_order_mark(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            0xBB => {
+                self.state = State::ByteOrderMarkEnd;
+                Ok(Action::Continue)
+            },
+            _ => {
+                Err(InternalError::UnexpectedByte)
+            },
+        }
+    }
+
+    fn run_fsm_byte_order_mark_end(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            0xBF => {
+                self.state = State::ScriptStart;
+                Ok(Action::Continue)
+            },
+            _ => {
+                Err(InternalError::UnexpectedByte)
+            },
+        }
+    }
+
+    fn run_fsm_hash(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'!' => {
+                self.state = State::Shebang;
+                Ok(Action::Continue)
+            },
+            _ => {
+                Err(InternalError::UnexpectedByte)
+            },
+        }
+    }
+
+    /// The position of an error met in the current state: the byte being
+    /// lexed, or the start of a byte order mark or `#!` line that turned out
+    /// not to be one.
+    fn error_position(self: &Self) -> Position {
+        match self.state {
+            State::ByteOrderMark | State::ByteOrderMarkEnd | State::Hash => self.prefix_start,
+            _ => self.position,
+        }
+    }
+
+    fn run_fsm_shebang(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        if byte == b'\n' {
+            self.state = State::Start;
         }
+        Ok(Action::Continue)
     }
 
     fn run_fsm_start(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
@@ -143,24 +822,71 @@ impl Lexer {
                 self.state = State::Integer;
                 return Ok(Action::Continue);
             },
-            b'+' => Token::Plus,
+            b'+' => {
+                self.state = State::Plus;
+                return Ok(Action::Continue);
+            },
             b'-' => {
                 self.state = State::Minus;
                 return Ok(Action::Continue);
             },
-            b'*' => Token::Asterisk,
-            b'/' => Token::ForwardSlash,
+            b'*' => {
+                self.state = State::Asterisk;
+                return Ok(Action::Continue);
+            },
+            b'/' => {
+                self.state = State::ForwardSlash;
+                return Ok(Action::Continue);
+            },
+            b'%' => {
+                self.state = State::Percent;
+                return Ok(Action::Continue);
+            },
+            b'&' => Token::Ampersand,
+            b'|' => {
+                self.state = State::VerticalBar;
+                return Ok(Action::Continue);
+            },
+            b'^' => Token::Caret,
+            b'~' => Token::Tilde,
             b'.' => {
                 self.state = State::Dot;
                 return Ok(Action::Continue);
             },
             b',' => Token::Comma,
-            b':' => Token::Colon,
+            b':' => {
+                self.state = State::Colon;
+                return Ok(Action::Continue);
+            },
             b';' => Token::Semicolon,
             b'=' => {
                 self.state = State::Equals;
                 return Ok(Action::Continue);
             },
+            b'<' => {
+                self.state = State::LessThan;
+                return Ok(Action::Continue);
+            },
+            b'>' => {
+                self.state = State::GreaterThan;
+                return Ok(Action::Continue);
+            },
+            b'!' => {
+                self.state = State::Exclamation;
+                return Ok(Action::Continue);
+            },
+            b'?' => {
+                self.state = State::Question;
+                return Ok(Action::Continue);
+            },
+            b'\'' => {
+                self.state = State::Character;
+                return Ok(Action::Continue);
+            },
+            b'"' => {
+                self.state = State::String;
+                return Ok(Action::Continue);
+            },
             b'(' => Token::LeftParenthesis,
             b')' => Token::RightParenthesis,
             b'[' => Token::LeftBracket,
@@ -171,29 +897,83 @@ impl Lexer {
                 return Err(InternalError::UnexpectedByte);
             },
         };
-        self.tokens.push(token);
+        self.emit(token);
         Ok(Action::Continue)
     }
 
-    fn classify_identifier(self: &mut Self) {
-        let token = match self.identifier.as_slice() {
-            b"and"      => Token::And,
-            b"else"     => Token::Else,
-            b"false"    => Token::False,
-            b"function" => Token::Function,
-            b"if"       => Token::If,
-            b"lambda"   => Token::Lambda,
-            b"let"      => Token::Let,
-            b"not"      => Token::Not,
-            b"or"       => Token::Or,
-            b"return"   => Token::Return,
-            b"true"     => Token::True,
-            b"xor"      => Token::Xor,
-            _           => Token::Identifier(Box::new(take(&mut self.identifier))),
-        };
+    /// Emits `token`, or only its kind when lexing borrowed tokens.
+    fn emit(self: &mut Self, token: Token) {
+        if self.payloads {
+            self.tokens.push(token);
+        } else {
+            self.kinds.push(token.kind());
+        }
+    }
 
-        self.identifier.clear();
-        self.tokens.push(token);
+    /// Emits a token whose payload `build` takes out of the lexer's buffers.
+    /// When lexing borrowed tokens the buffers are cleared instead, keeping
+    /// their capacity, so that no token allocates.
+    fn emit_payload(self: &mut Self, kind: TokenKind, build: impl FnOnce(&mut Self) -> Token) {
+        if self.payloads {
+            let token = build(self);
+            self.tokens.push(token);
+        } else {
+            self.integer.clear();
+            self.fractional.clear();
+            self.exponent_sign = Sign::Positive;
+            self.exponent.clear();
+            self.identifier.clear();
+            self.literal.clear();
+            self.segments.clear();
+            self.kinds.push(kind);
+        }
+    }
+
+    fn emit_integer(self: &mut Self, representation: fn(Vec<u8>) -> IntegerRepresentation) {
+        self.emit_payload(TokenKind::Integer, |lexer| {
+            Token::Integer(Box::new(representation(take(&mut lexer.integer))))
+        });
+    }
+
+    /// Emits the float in the number buffers, which is in scientific notation
+    /// if it has exponent digits.
+    fn emit_float(self: &mut Self) {
+        self.emit_payload(TokenKind::Float, |lexer| {
+            let float = if lexer.exponent.len() == 0 {
+                FloatRepresentation::Decimal {
+                    integer: take(&mut lexer.integer),
+                    fractional: take(&mut lexer.fractional),
+                }
+            } else {
+                FloatRepresentation::Scientific {
+                    integer: take(&mut lexer.integer),
+                    fractional: take(&mut lexer.fractional),
+                    exponent_sign: take(&mut lexer.exponent_sign),
+                    exponent: take(&mut lexer.exponent),
+                }
+            };
+            Token::Float(Box::new(float))
+        });
+    }
+
+    fn classify_identifier(self: &mut Self) {
+        let identifier = self.identifier.as_slice();
+        match builtin_keyword(identifier) {
+            Some(token) if !self.options.disabled_keywords.iter().any(|keyword| keyword == identifier) => {
+                self.identifier.clear();
+                self.emit(token);
+            },
+            _ if self.options.keywords.iter().any(|keyword| keyword == identifier) => {
+                self.emit_payload(TokenKind::Extension, |lexer| {
+                    Token::Extension(Box::new(take(&mut lexer.identifier)))
+                });
+            },
+            _ => {
+                self.emit_payload(TokenKind::Identifier, |lexer| {
+                    Token::Identifier(Box::new(take(&mut lexer.identifier)))
+                });
+            },
+        }
     }
 
     fn run_fsm_identifier(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
@@ -229,6 +1009,12 @@ impl Lexer {
                 self.state = State::Fractional;
                 Ok(Action::Continue)
             },
+            b'0'..=b'9' if self.options.allow_leading_zeros => {
+                self.integer.push(0);
+                self.integer.push(byte - b'0');
+                self.state = State::Integer;
+                Ok(Action::Continue)
+            },
             b'0'..=b'9' => {
                 Err(InternalError::LeadingZeroWithoutBase)
             },
@@ -236,8 +1022,8 @@ impl Lexer {
                 Err(InternalError::InvalidNumberDigit)
             },
             _ => {
-                let integer = IntegerRepresentation::Decimal(vec![0]);
-                self.tokens.push(Token::Integer(Box::new(integer)));
+                self.integer.push(0);
+                self.emit_integer(IntegerRepresentation::Decimal);
                 self.state = State::Start;
                 Ok(Action::Again)
             },
@@ -252,7 +1038,7 @@ impl Lexer {
                 Ok(Action::Continue)
             },
             _ => {
-                self.tokens.push(Token::Dot);
+                self.emit(Token::Dot);
                 self.state = State::Start;
                 Ok(Action::Again)
             },
@@ -274,8 +1060,7 @@ impl Lexer {
                 Ok(Action::Continue)
             }
             _ => {
-                let integer = IntegerRepresentation::Decimal(take(&mut self.integer));
-                self.tokens.push(Token::Integer(Box::new(integer)));
+                self.emit_integer(IntegerRepresentation::Decimal);
                 self.state = State::Start;
                 Ok(Action::Again)
             },
@@ -303,8 +1088,7 @@ impl Lexer {
                 if self.integer.len() == 0 {
                     Err(InternalError::MissingDigitsAfterBasePrefix)
                 } else {
-                    let integer = IntegerRepresentation::Hexadecimal(take(&mut self.integer));
-                    self.tokens.push(Token::Integer(Box::new(integer)));
+                    self.emit_integer(IntegerRepresentation::Hexadecimal);
                     self.state = State::Start;
                     Ok(Action::Again)
                 }
@@ -325,8 +1109,7 @@ impl Lexer {
                 if self.integer.len() == 0 {
                     Err(InternalError::MissingDigitsAfterBasePrefix)
                 } else {
-                    let integer = IntegerRepresentation::Octal(take(&mut self.integer));
-                    self.tokens.push(Token::Integer(Box::new(integer)));
+                    self.emit_integer(IntegerRepresentation::Octal);
                     self.state = State::Start;
                     Ok(Action::Again)
                 }
@@ -347,8 +1130,7 @@ impl Lexer {
                 if self.integer.len() == 0 {
                     Err(InternalError::MissingDigitsAfterBasePrefix)
                 } else {
-                    let integer = IntegerRepresentation::Binary(take(&mut self.integer));
-                    self.tokens.push(Token::Integer(Box::new(integer)));
+                    self.emit_integer(IntegerRepresentation::Binary);
                     self.state = State::Start;
                     Ok(Action::Again)
                 }
@@ -367,11 +1149,7 @@ impl Lexer {
                 Ok(Action::Continue)
             }
             _ => {
-                let float = FloatRepresentation::Decimal {
-                    integer: take(&mut self.integer),
-                    fractional: take(&mut self.fractional),
-                };
-                self.tokens.push(Token::Float(Box::new(float)));
+                self.emit_float();
                 self.state = State::Start;
                 Ok(Action::Again)
             },
@@ -384,15 +1162,34 @@ impl Lexer {
                 self.exponent.push(byte - b'0');
                 Ok(Action::Continue)
             },
+            b'+' | b'-' if self.exponent.len() == 0 => {
+                if byte == b'-' {
+                    self.exponent_sign = Sign::Negative;
+                }
+                self.state = State::ExponentSign;
+                Ok(Action::Continue)
+            },
             _ => {
-                let float = FloatRepresentation::Scientific {
-                    integer: take(&mut self.integer),
-                    fractional: take(&mut self.fractional),
-                    exponent: take(&mut self.exponent),
-                };
-                self.tokens.push(Token::Float(Box::new(float)));
-                self.state = State::Start;
-                Ok(Action::Again)
+                if self.exponent.len() == 0 {
+                    Err(InternalError::MissingDigitsAfterExponentMark)
+                } else {
+                    self.emit_float();
+                    self.state = State::Start;
+                    Ok(Action::Again)
+                }
+            },
+        }
+    }
+
+    fn run_fsm_exponent_sign(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'0'..=b'9' => {
+                self.exponent.push(byte - b'0');
+                self.state = State::Exponent;
+                Ok(Action::Continue)
+            },
+            _ => {
+                Err(InternalError::MissingDigitsAfterExponentMark)
             },
         }
     }
@@ -400,49 +1197,489 @@ impl Lexer {
     fn run_fsm_equals(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
         match byte {
             b'=' => {
-                self.tokens.push(Token::Equals);
+                self.emit(Token::Equals);
+                self.state = State::Start;
                 Ok(Action::Continue)
             },
             _ => {
-                self.tokens.push(Token::Assign);
+                self.emit(Token::Assign);
                 self.state = State::Start;
                 Ok(Action::Again)
             },
         }
     }
 
-    fn run_fsm_minus(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+    fn run_fsm_plus(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
         match byte {
-            b'>' => {
-                self.tokens.push(Token::RightArrow);
+            b'=' => {
+                self.emit(Token::PlusAssign);
+                self.state = State::Start;
                 Ok(Action::Continue)
             },
             _ => {
-                self.tokens.push(Token::Minus);
+                self.emit(Token::Plus);
                 self.state = State::Start;
                 Ok(Action::Again)
             },
         }
     }
 
-    fn run_fsm(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
-        match self.state {
-            State::Start        => self.run_fsm_start(byte),
-            State::Identifier   => self.run_fsm_identifier(byte),
-            State::Zero         => self.run_fsm_zero(byte),
-            State::Dot          => self.run_fsm_dot(byte),
-            State::Integer      => self.run_fsm_integer(byte),
-            State::Hexadecimal  => self.run_fsm_hexadecimal(byte),
-            State::Octal        => self.run_fsm_octal(byte),
-            State::Binary       => self.run_fsm_binary(byte),
-            State::Fractional   => self.run_fsm_fractional(byte),
-            State::Exponent     => self.run_fsm_exponent(byte),
-            State::Equals       => self.run_fsm_equals(byte),
-            State::Minus        => self.run_fsm_minus(byte),
+    fn run_fsm_minus(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'>' => {
+                self.emit(Token::RightArrow);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            b'=' => {
+                self.emit(Token::MinusAssign);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::Minus);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
         }
     }
 
-    fn feed_byte(self: &mut Self, byte: u8) -> Result<(), InternalError> {
+    fn run_fsm_asterisk(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'=' => {
+                self.emit(Token::AsteriskAssign);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::Asterisk);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_forward_slash(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'/' => {
+                self.state = State::DoubleForwardSlash;
+                Ok(Action::Continue)
+            },
+            b'=' => {
+                self.emit(Token::ForwardSlashAssign);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::ForwardSlash);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_double_forward_slash(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'=' => {
+                self.emit(Token::DoubleForwardSlashAssign);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::DoubleForwardSlash);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_percent(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'=' => {
+                self.emit(Token::PercentAssign);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::Percent);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_colon(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b':' => {
+                self.emit(Token::DoubleColon);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::Colon);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_vertical_bar(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'>' => {
+                self.emit(Token::Pipeline);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::VerticalBar);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_less_than(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'=' => {
+                self.emit(Token::LessThanOrEquals);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            b'<' => {
+                self.emit(Token::LeftShift);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::LessThan);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_greater_than(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'=' => {
+                self.emit(Token::GreaterThanOrEquals);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            b'>' => {
+                self.emit(Token::RightShift);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.emit(Token::GreaterThan);
+                self.state = State::Start;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    fn run_fsm_exclamation(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'=' => {
+                self.emit(Token::NotEquals);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                Err(InternalError::UnexpectedByte)
+            },
+        }
+    }
+
+    fn run_fsm_question(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'.' => {
+                self.emit(Token::QuestionDot);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            b'?' => {
+                self.emit(Token::DoubleQuestion);
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            _ => {
+                Err(InternalError::UnexpectedByte)
+            },
+        }
+    }
+
+    fn run_fsm_character(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'\'' => {
+                let character = decode_character(&self.literal)
+                    .ok_or(InternalError::InvalidCharacterLiteral)?;
+                self.literal.clear();
+                self.emit(Token::Character(character));
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            b'\\' => {
+                self.literal_state = State::Character;
+                self.state = State::Escape;
+                Ok(Action::Continue)
+            },
+            b'\n' => {
+                Err(InternalError::UnterminatedCharacterLiteral)
+            },
+            _ => {
+                self.literal.push(byte);
+                Ok(Action::Continue)
+            },
+        }
+    }
+
+    fn run_fsm_string(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'"' => {
+                if self.segments.len() == 0 {
+                    self.emit_payload(TokenKind::String, |lexer| {
+                        Token::String(Box::new(take(&mut lexer.literal)))
+                    });
+                } else {
+                    self.emit_payload(TokenKind::InterpolatedString, |lexer| {
+                        if lexer.literal.len() != 0 {
+                            lexer.segments.push(StringSegment::Literal(take(&mut lexer.literal)));
+                        }
+                        Token::InterpolatedString(Box::new(take(&mut lexer.segments)))
+                    });
+                }
+                self.state = State::Start;
+                Ok(Action::Continue)
+            },
+            b'\\' => {
+                self.literal_state = State::String;
+                self.state = State::Escape;
+                Ok(Action::Continue)
+            },
+            b'$' => {
+                self.state = State::StringDollar;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.literal.push(byte);
+                Ok(Action::Continue)
+            },
+        }
+    }
+
+    fn run_fsm_escape(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        let decoded = match byte {
+            b'n'    => b'\n',
+            b'r'    => b'\r',
+            b't'    => b'\t',
+            b'0'    => b'\0',
+            b'\\'   => b'\\',
+            b'\''   => b'\'',
+            b'"'    => b'"',
+            b'$'    => b'$',
+            b'x' => {
+                self.state = State::HexadecimalEscape;
+                return Ok(Action::Continue);
+            },
+            b'u' => {
+                self.state = State::UnicodeEscape;
+                return Ok(Action::Continue);
+            },
+            _ => {
+                return Err(InternalError::InvalidEscapeSequence);
+            },
+        };
+        self.literal.push(decoded);
+        self.state = self.literal_state;
+        Ok(Action::Continue)
+    }
+
+    fn run_fsm_hexadecimal_escape(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        let digit = hexadecimal_digit(byte).ok_or(InternalError::InvalidEscapeSequence)?;
+        self.escape.push(digit);
+        if self.escape.len() == 2 {
+            self.literal.push(self.escape[0] << 4 | self.escape[1]);
+            self.escape.clear();
+            self.state = self.literal_state;
+        }
+        Ok(Action::Continue)
+    }
+
+    fn run_fsm_unicode_escape(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'{' => {
+                self.state = State::UnicodeEscapeDigits;
+                Ok(Action::Continue)
+            },
+            _ => {
+                Err(InternalError::InvalidEscapeSequence)
+            },
+        }
+    }
+
+    fn run_fsm_unicode_escape_digits(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'}' => {
+                if self.escape.len() == 0 {
+                    return Err(InternalError::InvalidEscapeSequence);
+                }
+                let code = self.escape.iter()
+                    .fold(0u32, |code, &digit| code << 4 | digit as u32);
+                let character = char::from_u32(code)
+                    .ok_or(InternalError::InvalidEscapeSequence)?;
+                let mut buffer = [0; 4];
+                self.literal.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                self.escape.clear();
+                self.state = self.literal_state;
+                Ok(Action::Continue)
+            },
+            _ => {
+                let digit = hexadecimal_digit(byte).ok_or(InternalError::InvalidEscapeSequence)?;
+                if self.escape.len() == 6 {
+                    return Err(InternalError::InvalidEscapeSequence);
+                }
+                self.escape.push(digit);
+                Ok(Action::Continue)
+            },
+        }
+    }
+
+    fn run_fsm_string_dollar(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match byte {
+            b'{' => {
+                if !self.payloads {
+                    self.literal.clear();
+                } else if self.literal.len() != 0 {
+                    self.segments.push(StringSegment::Literal(take(&mut self.literal)));
+                }
+                self.interpolation = Some(Box::new(Lexer {
+                    state: State::Start,
+                    payloads: self.payloads,
+                    options: self.options.clone(),
+                    ..Lexer::new()
+                }));
+                self.state = State::Interpolation;
+                Ok(Action::Continue)
+            },
+            _ => {
+                self.literal.push(b'$');
+                self.state = State::String;
+                Ok(Action::Again)
+            },
+        }
+    }
+
+    /// Feeds the bytes of a `${...}` expression to a nested lexer, until the
+    /// `}` balancing the opening brace is found outside of any literal.
+    fn run_fsm_interpolation(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        let interpolation = self.interpolation.as_mut().unwrap();
+        if !interpolation.in_literal() {
+            match byte {
+                b'{' => {
+                    self.interpolation_depth += 1;
+                },
+                b'}' if self.interpolation_depth == 0 => {
+                    interpolation.flush()?;
+                    let tokens = take(&mut interpolation.tokens);
+                    self.segments.push(StringSegment::Tokens(tokens));
+                    self.interpolation = None;
+                    self.state = State::String;
+                    return Ok(Action::Continue);
+                },
+                b'}' => {
+                    self.interpolation_depth -= 1;
+                },
+                _ => {},
+            }
+        }
+        interpolation.feed_byte(byte)?;
+        Ok(Action::Continue)
+    }
+
+    /// Lists the literals and `${...}` expressions the lexer is inside of,
+    /// outermost first.
+    fn enclosing(self: &Self, enclosing: &mut Vec<Enclosing>) {
+        let quote = match self.state {
+            State::Character => b'\'',
+            State::Escape
+            | State::HexadecimalEscape
+            | State::UnicodeEscape
+            | State::UnicodeEscapeDigits if matches!(self.literal_state, State::Character) => b'\'',
+            _ if self.in_literal() => b'"',
+            _ => return,
+        };
+        enclosing.push(Enclosing::Quote(quote));
+        if let Some(interpolation) = &self.interpolation {
+            enclosing.push(Enclosing::Interpolation(self.interpolation_depth));
+            interpolation.enclosing(enclosing);
+        }
+    }
+
+    /// The lexer of the innermost `${...}` expression being lexed, or this
+    /// one.
+    fn innermost(self: &Self) -> &Lexer {
+        match &self.interpolation {
+            Some(interpolation) => interpolation.innermost(),
+            None => self,
+        }
+    }
+
+    fn in_literal(self: &Self) -> bool {
+        matches!(self.state,
+            State::Character
+            | State::String
+            | State::Escape
+            | State::HexadecimalEscape
+            | State::UnicodeEscape
+            | State::UnicodeEscapeDigits
+            | State::StringDollar
+            | State::Interpolation)
+    }
+
+    fn run_fsm(self: &mut Self, byte: u8) -> Result<Action, InternalError> {
+        match self.state {
+            State::ScriptStart          => self.run_fsm_script_start(byte),
+            State::ByteOrderMark        => self.run_fsm_byte_order_mark(byte),
+            State::ByteOrderMarkEnd     => self.run_fsm_byte_order_mark_end(byte),
+            State::Hash                 => self.run_fsm_hash(byte),
+            State::Shebang              => self.run_fsm_shebang(byte),
+            State::Start                => self.run_fsm_start(byte),
+            State::Identifier           => self.run_fsm_identifier(byte),
+            State::Zero                 => self.run_fsm_zero(byte),
+            State::Dot                  => self.run_fsm_dot(byte),
+            State::Integer              => self.run_fsm_integer(byte),
+            State::Hexadecimal          => self.run_fsm_hexadecimal(byte),
+            State::Octal                => self.run_fsm_octal(byte),
+            State::Binary               => self.run_fsm_binary(byte),
+            State::Fractional           => self.run_fsm_fractional(byte),
+            State::Exponent             => self.run_fsm_exponent(byte),
+            State::ExponentSign         => self.run_fsm_exponent_sign(byte),
+            State::Equals               => self.run_fsm_equals(byte),
+            State::Plus                 => self.run_fsm_plus(byte),
+            State::Minus                => self.run_fsm_minus(byte),
+            State::Asterisk             => self.run_fsm_asterisk(byte),
+            State::ForwardSlash         => self.run_fsm_forward_slash(byte),
+            State::DoubleForwardSlash   => self.run_fsm_double_forward_slash(byte),
+            State::Percent              => self.run_fsm_percent(byte),
+            State::Colon                => self.run_fsm_colon(byte),
+            State::VerticalBar          => self.run_fsm_vertical_bar(byte),
+            State::LessThan             => self.run_fsm_less_than(byte),
+            State::GreaterThan          => self.run_fsm_greater_than(byte),
+            State::Exclamation          => self.run_fsm_exclamation(byte),
+            State::Question             => self.run_fsm_question(byte),
+            State::Character            => self.run_fsm_character(byte),
+            State::String               => self.run_fsm_string(byte),
+            State::Escape               => self.run_fsm_escape(byte),
+            State::HexadecimalEscape    => self.run_fsm_hexadecimal_escape(byte),
+            State::UnicodeEscape        => self.run_fsm_unicode_escape(byte),
+            State::UnicodeEscapeDigits  => self.run_fsm_unicode_escape_digits(byte),
+            State::StringDollar         => self.run_fsm_string_dollar(byte),
+            State::Interpolation        => self.run_fsm_interpolation(byte),
+        }
+    }
+
+    fn feed_byte(self: &mut Self, byte: u8) -> Result<(), InternalError> {
         loop {
             match self.run_fsm(byte) {
                 Ok(action) => match action {
@@ -455,123 +1692,624 @@ impl Lexer {
     }
 
     fn feed_script(self: &mut Self, script: &[u8]) -> Result<(), Error> {
-        for i in 0..script.len() {
-            let byte = script[i];
-            match self.feed_byte(byte) {
-                Ok(()) => continue,
-                Err(error) => return match error {
-                    InternalError::UnexpectedByte =>
-                        Err(Error::UnexpectedByte(i)),
-                    InternalError::InvalidNumberDigit =>
-                        Err(Error::InvalidNumberDigit(i)),
-                    InternalError::LeadingZeroWithoutBase =>
-                        Err(Error::LeadingZeroWithoutBase(i)),
-                    InternalError::InvalidHexadecimalDigit =>
-                        Err(Error::InvalidHexadecimalDigit(i)),
-                    InternalError::InvalidOctalDigit =>
-                        Err(Error::InvalidOctalDigit(i)),
-                    InternalError::InvalidBinaryDigit =>
-                        Err(Error::InvalidBinaryDigit(i)),
-                    InternalError::MissingDigitsAfterBasePrefix =>
-                        Err(Error::MissingDigitsAfterBasePrefix(i)),
+        for &byte in script {
+            self.feed_byte(byte).map_err(|error| error.at(self.error_position()))?;
+            self.position.advance(byte);
+        }
+
+        Ok(())
+    }
+
+    /// Emits the token pending in the current state, as if the input ended
+    /// here.
+    fn flush(self: &mut Self) -> Result<(), InternalError> {
+        match self.state {
+            State::ScriptStart | State::Shebang | State::Start => {
+                Ok(())
+            },
+            State::ByteOrderMark | State::ByteOrderMarkEnd | State::Hash => {
+                Err(InternalError::UnexpectedByte)
+            },
+            State::Identifier => {
+                self.classify_identifier();
+                Ok(())
+            },
+            State::Zero => {
+                self.integer.push(0);
+                self.emit_integer(IntegerRepresentation::Decimal);
+                Ok(())
+            },
+            State::Dot => {
+                self.emit(Token::Dot);
+                Ok(())
+            },
+            State::Integer => {
+                self.emit_integer(IntegerRepresentation::Decimal);
+                Ok(())
+            },
+            State::Hexadecimal => {
+                if self.integer.len() == 0 {
+                    Err(InternalError::MissingDigitsAfterBasePrefix)
+                } else {
+                    self.emit_integer(IntegerRepresentation::Hexadecimal);
+                    Ok(())
+                }
+            },
+            State::Octal => {
+                if self.integer.len() == 0 {
+                    Err(InternalError::MissingDigitsAfterBasePrefix)
+                } else {
+                    self.emit_integer(IntegerRepresentation::Octal);
+                    Ok(())
+                }
+            },
+            State::Binary => {
+                if self.integer.len() == 0 {
+                    Err(InternalError::MissingDigitsAfterBasePrefix)
+                } else {
+                    self.emit_integer(IntegerRepresentation::Binary);
+                    Ok(())
+                }
+            },
+            State::Fractional => {
+                self.emit_float();
+                Ok(())
+            },
+            State::Exponent => {
+                if self.exponent.len() == 0 {
+                    Err(InternalError::MissingDigitsAfterExponentMark)
+                } else {
+                    self.emit_float();
+                    Ok(())
+                }
+            },
+            State::ExponentSign => {
+                Err(InternalError::MissingDigitsAfterExponentMark)
+            },
+            State::Equals => {
+                self.emit(Token::Assign);
+                Ok(())
+            },
+            State::Plus => {
+                self.emit(Token::Plus);
+                Ok(())
+            },
+            State::Minus => {
+                self.emit(Token::Minus);
+                Ok(())
+            },
+            State::Asterisk => {
+                self.emit(Token::Asterisk);
+                Ok(())
+            },
+            State::ForwardSlash => {
+                self.emit(Token::ForwardSlash);
+                Ok(())
+            },
+            State::DoubleForwardSlash => {
+                self.emit(Token::DoubleForwardSlash);
+                Ok(())
+            },
+            State::Percent => {
+                self.emit(Token::Percent);
+                Ok(())
+            },
+            State::Colon => {
+                self.emit(Token::Colon);
+                Ok(())
+            },
+            State::VerticalBar => {
+                self.emit(Token::VerticalBar);
+                Ok(())
+            },
+            State::LessThan => {
+                self.emit(Token::LessThan);
+                Ok(())
+            },
+            State::GreaterThan => {
+                self.emit(Token::GreaterThan);
+                Ok(())
+            },
+            State::Exclamation | State::Question => {
+                Err(InternalError::UnexpectedByte)
+            },
+            State::Character => {
+                Err(InternalError::UnterminatedCharacterLiteral)
+            },
+            State::String | State::StringDollar | State::Interpolation => {
+                Err(InternalError::UnterminatedStringLiteral)
+            },
+            State::Escape
+            | State::HexadecimalEscape
+            | State::UnicodeEscape
+            | State::UnicodeEscapeDigits => match self.literal_state {
+                State::Character => Err(InternalError::UnterminatedCharacterLiteral),
+                _ => Err(InternalError::UnterminatedStringLiteral),
+            },
+        }
+    }
+
+    /// Drops any partially lexed token, keeping completed tokens and the
+    /// current position.
+    fn reset(self: &mut Self) {
+        self.state = State::Start;
+        self.integer.clear();
+        self.fractional.clear();
+        self.exponent_sign = Sign::Positive;
+        self.exponent.clear();
+        self.identifier.clear();
+        self.literal.clear();
+        self.escape.clear();
+        self.segments.clear();
+        self.interpolation = None;
+        self.interpolation_depth = 0;
+    }
+
+    fn feed_eof(self: &mut Self) -> Result<(), Error> {
+        self.flush().map_err(|error| error.at(self.error_position()))
+    }
+}
+
+/// Tokenizes a script that arrives in chunks, e.g. from a socket.
+///
+/// Chunks may split tokens anywhere; error positions count from the start
+/// of the first chunk. Once an error has been returned, the lexer must not be
+/// used any further.
+pub struct StreamingLexer {
+    lexer: Lexer,
+}
+
+impl Default for StreamingLexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamingLexer {
+    pub fn new() -> Self {
+        Self {
+            lexer: Lexer::new(),
+        }
+    }
+
+    pub fn push_bytes(self: &mut Self, bytes: &[u8]) -> Result<(), Error> {
+        self.lexer.feed_script(bytes)
+    }
+
+    /// Takes the tokens completed so far, leaving any partial token pending.
+    pub fn take_tokens(self: &mut Self) -> Vec<Token> {
+        take(&mut self.lexer.tokens)
+    }
+
+    /// Ends the input, returning the tokens not taken yet.
+    pub fn finish(mut self: Self) -> Result<Vec<Token>, Error> {
+        self.lexer.feed_eof()?;
+        Ok(take(&mut self.lexer.tokens))
+    }
+}
+
+/// Decodes the bytes between the quotes of a character literal, escapes
+/// already resolved, which must be a single UTF-8 encoded character.
+fn decode_character(literal: &[u8]) -> Option<char> {
+    let mut characters = std::str::from_utf8(literal).ok()?.chars();
+    let character = characters.next()?;
+    match characters.next() {
+        Some(_) => None,
+        None => Some(character),
+    }
+}
+
+fn builtin_keyword(identifier: &[u8]) -> Option<Token> {
+    let token = match identifier {
+        b"and"      => Token::And,
+        b"break"    => Token::Break,
+        b"const"    => Token::Const,
+        b"continue" => Token::Continue,
+        b"else"     => Token::Else,
+        b"enum"     => Token::Enum,
+        b"false"    => Token::False,
+        b"for"      => Token::For,
+        b"function" => Token::Function,
+        b"if"       => Token::If,
+        b"import"   => Token::Import,
+        b"in"       => Token::In,
+        b"lambda"   => Token::Lambda,
+        b"let"      => Token::Let,
+        b"match"    => Token::Match,
+        b"mut"      => Token::Mut,
+        b"not"      => Token::Not,
+        b"or"       => Token::Or,
+        b"return"   => Token::Return,
+        b"struct"   => Token::Struct,
+        b"then"     => Token::Then,
+        b"true"     => Token::True,
+        b"while"    => Token::While,
+        b"xor"      => Token::Xor,
+        _           => return None,
+    };
+    Some(token)
+}
+
+fn hexadecimal_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'A'..=b'F' => Some(10 + (byte - b'A')),
+        b'a'..=b'f' => Some(10 + (byte - b'a')),
+        _ => None,
+    }
+}
+
+/// Lexes a script lazily, yielding each token as soon as it is complete.
+///
+/// Iteration stops after the first error.
+pub struct TokenIterator<'a> {
+    script: &'a [u8],
+    lexer: Lexer,
+    pending: VecDeque<Token>,
+    finished: bool,
+}
+
+impl<'a> TokenIterator<'a> {
+    pub fn new(script: &'a [u8]) -> Self {
+        Self {
+            script,
+            lexer: Lexer::new(),
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+}
+
+impl<'a> Iterator for TokenIterator<'a> {
+    type Item = Result<Token, Error>;
+
+    fn next(self: &mut Self) -> Option<Self::Item> {
+        while self.pending.len() == 0 && !self.finished {
+            let result = match self.script.get(self.lexer.position.offset) {
+                Some(&byte) => self.lexer.feed_script(&[byte]),
+                None => {
+                    self.finished = true;
+                    self.lexer.feed_eof()
                 },
+            };
+            if let Err(error) = result {
+                self.finished = true;
+                return Some(Err(error));
             }
+            self.pending.extend(self.lexer.tokens.drain(..));
         }
 
-        Ok(())
+        self.pending.pop_front().map(Ok)
     }
+}
 
-    fn feed_eof(self: &mut Self, script: &[u8]) -> Result<(), Error> {
-        let script_len = script.len();
-        match self.state {
-            State::Start => {
-                Ok(())
-            },
-            State::Identifier => {
-                self.classify_identifier();
-                Ok(())
+/// Bytes at which lexing resumes after an error.
+fn is_recovery_point(byte: u8) -> bool {
+    matches!(byte,
+        b' ' | b'\t' | b'\r' | b'\n'
+        | b';' | b',' | b'(' | b')' | b'[' | b']' | b'{' | b'}')
+}
+
+/// A literal or `${...}` expression skipped after an error.
+enum Enclosing {
+    /// A literal closed by the given quote.
+    Quote(u8),
+    /// An interpolated expression, with the number of unbalanced `{` in it.
+    Interpolation(usize),
+}
+
+/// Skips to the end of the outermost of the `enclosing` literals, past its
+/// closing quote. A character literal also ends at the end of its line.
+fn skip_literal(script: &[u8], position: &mut Position, mut enclosing: Vec<Enclosing>) {
+    while let (Some(&byte), Some(innermost)) = (script.get(position.offset), enclosing.last_mut()) {
+        match (innermost, byte) {
+            (Enclosing::Quote(b'\''), b'\n') => {
+                return;
             },
-            State::Zero => {
-                let integer = IntegerRepresentation::Decimal(vec![0]);
-                self.tokens.push(Token::Integer(Box::new(integer)));
-                Ok(())
+            (Enclosing::Quote(_), b'\\') => {
+                position.advance(byte);
+                let Some(&byte) = script.get(position.offset) else { return };
+                position.advance(byte);
+                continue;
             },
-            State::Dot => {
-                self.tokens.push(Token::Dot);
-                Ok(())
+            (Enclosing::Quote(quote), _) if byte == *quote => {
+                enclosing.pop();
             },
-            State::Integer => {
-                let integer = IntegerRepresentation::Decimal(take(&mut self.integer));
-                self.tokens.push(Token::Integer(Box::new(integer)));
-                Ok(())
+            (Enclosing::Quote(b'"'), b'$') if script.get(position.offset + 1) == Some(&b'{') => {
+                position.advance(byte);
+                enclosing.push(Enclosing::Interpolation(0));
             },
-            State::Hexadecimal => {
-                if self.integer.len() == 0 {
-                    Err(Error::MissingDigitsAfterBasePrefix(script_len))
-                } else {
-                    let integer = IntegerRepresentation::Hexadecimal(take(&mut self.integer));
-                    self.tokens.push(Token::Integer(Box::new(integer)));
-                    Ok(())
-                }
+            (Enclosing::Quote(_), _) => {},
+            (Enclosing::Interpolation(0), b'}') => {
+                enclosing.pop();
             },
-            State::Octal => {
-                if self.integer.len() == 0 {
-                    Err(Error::MissingDigitsAfterBasePrefix(script_len))
-                } else {
-                    let integer = IntegerRepresentation::Octal(take(&mut self.integer));
-                    self.tokens.push(Token::Integer(Box::new(integer)));
-                    Ok(())
-                }
+            (Enclosing::Interpolation(depth), b'}') => {
+                *depth -= 1;
             },
-            State::Binary => {
-                if self.integer.len() == 0 {
-                    Err(Error::MissingDigitsAfterBasePrefix(script_len))
-                } else {
-                    let integer = IntegerRepresentation::Binary(take(&mut self.integer));
-                    self.tokens.push(Token::Integer(Box::new(integer)));
-                    Ok(())
-                }
+            (Enclosing::Interpolation(depth), b'{') => {
+                *depth += 1;
             },
-            State::Fractional => {
-                let float = FloatRepresentation::Decimal {
-                    integer: take(&mut self.integer),
-                    fractional: take(&mut self.fractional),
-                };
-                self.tokens.push(Token::Float(Box::new(float)));
-                Ok(())
+            (Enclosing::Interpolation(_), b'"' | b'\'') => {
+                enclosing.push(Enclosing::Quote(byte));
             },
-            State::Exponent => {
-                if self.exponent.len() == 0 {
-                    Err(Error::MissingDigitsAfterExponentMark(script_len))
-                } else {
-                    let float = FloatRepresentation::Scientific {
-                        integer: take(&mut self.integer),
-                        fractional: take(&mut self.fractional),
-                        exponent: take(&mut self.exponent),
-                    };
-                    self.tokens.push(Token::Float(Box::new(float)));
-                    Ok(())
+            (Enclosing::Interpolation(_), _) => {},
+        }
+        position.advance(byte);
+    }
+}
+
+/// Tokenizes a whole script, recovering from errors instead of stopping at
+/// the first one: the partial token is dropped and lexing resumes at the
+/// next whitespace or delimiter, or after the closing quote of a literal the
+/// error is in. Returns every token lexed and every error met, in source
+/// order.
+pub fn tokenize_with_recovery(script: &[u8]) -> (Vec<Token>, Vec<Error>) {
+    let mut lexer = Lexer::new();
+    let mut errors = vec![];
+    while lexer.position.offset < script.len() {
+        if let Err(error) = lexer.feed_script(&script[lexer.position.offset..]) {
+            errors.push(error);
+            let mut enclosing = vec![];
+            lexer.enclosing(&mut enclosing);
+            if matches!(lexer.innermost().state, State::Escape) {
+                // The byte after a `\` is not taken for a quote.
+                lexer.position.advance(script[lexer.position.offset]);
+            }
+            lexer.reset();
+            if enclosing.len() != 0 {
+                skip_literal(script, &mut lexer.position, enclosing);
+                continue;
+            }
+            while let Some(&byte) = script.get(lexer.position.offset) {
+                if is_recovery_point(byte) {
+                    break;
                 }
-            },
-            State::Equals => {
-                self.tokens.push(Token::Assign);
-                Ok(())
-            },
-            State::Minus => {
-                self.tokens.push(Token::Minus);
-                Ok(())
-            },
+                lexer.position.advance(byte);
+            }
         }
     }
+    if let Err(error) = lexer.feed_eof() {
+        errors.push(error);
+    }
+
+    (take(&mut lexer.tokens), errors)
 }
 
-pub fn tokenize(script: &[u8]) -> Result<Vec<Token>, Error> {
+/// A token together with the exact source text it was lexed from and the
+/// whitespace around it. Trailing trivia runs up to and including the end of
+/// the token's line; everything after that leads the next token.
+#[derive(Debug, PartialEq)]
+pub struct TriviaToken {
+    pub leading: Vec<u8>,
+    pub token: Token,
+    pub text: Vec<u8>,
+    pub trailing: Vec<u8>,
+}
+
+/// Tokenizes a script keeping its trivia, so that concatenating `leading`,
+/// `text` and `trailing` of every token reproduces the script byte for byte.
+/// The last token is always `Token::EOF`, leading the whitespace at the end
+/// of the script.
+pub fn tokenize_with_trivia(script: &[u8]) -> Result<Vec<TriviaToken>, Error> {
     let mut lexer = Lexer::new();
+    let spans = feed_script_with_spans(&mut lexer, script)?;
+
+    let mut tokens: Vec<TriviaToken> = vec![];
+    let mut trivia_start = 0;
+    let eof = Some((Token::EOF, script.len()..script.len()));
+    for (token, span) in take(&mut lexer.tokens).into_iter().zip(spans).chain(eof) {
+        let mut leading = &script[trivia_start..span.start];
+        if let Some(previous) = tokens.last_mut() {
+            let line_end = leading.iter().position(|&byte| byte == b'\n')
+                .map_or(leading.len(), |index| index + 1);
+            previous.trailing = leading[..line_end].to_vec();
+            leading = &leading[line_end..];
+        }
+        tokens.push(TriviaToken {
+            leading: leading.to_vec(),
+            token,
+            text: script[span.clone()].to_vec(),
+            trailing: vec![],
+        });
+        trivia_start = span.end;
+    }
+
+    Ok(tokens)
+}
+
+/// Feeds a whole script to `lexer`, returning the source range of every
+/// token it emits.
+fn feed_script_with_spans(lexer: &mut Lexer, script: &[u8]) -> Result<Vec<Range<usize>>, Error> {
+    let emitted = |lexer: &Lexer| lexer.tokens.len() + lexer.kinds.len();
+    let mut spans = vec![];
+    let mut start = 0;
+    for (offset, &byte) in script.iter().enumerate() {
+        loop {
+            if matches!(lexer.state, State::Start) {
+                start = offset;
+            }
+            let count = emitted(lexer);
+            let action = lexer.run_fsm(byte).map_err(|error| error.at(lexer.error_position()))?;
+            let end = match action {
+                Action::Continue => offset + 1,
+                Action::Again => offset,
+            };
+            spans.extend((count..emitted(lexer)).map(|_| start..end));
+            if let Action::Continue = action {
+                break;
+            }
+        }
+        lexer.position.advance(byte);
+    }
+    let count = emitted(lexer);
+    lexer.feed_eof()?;
+    spans.extend((count..emitted(lexer)).map(|_| start..script.len()));
+
+    Ok(spans)
+}
+
+/// A token that borrows its text from the script instead of owning a
+/// payload. `text` is the exact source of the token, so string literals keep
+/// their quotes and escapes undecoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BorrowedToken<'src> {
+    pub kind: TokenKind,
+    pub text: &'src [u8],
+}
+
+/// Tokenizes a script without allocating per token, for hosts that only need
+/// kinds and source text, e.g. to highlight or pre-screen many small
+/// scripts. Only the returned vector and the nested lexer of each `${...}`
+/// interpolation allocate.
+pub fn tokenize_borrowed(script: &[u8]) -> Result<Vec<BorrowedToken<'_>>, Error> {
+    let mut lexer = Lexer { payloads: false, ..Lexer::new() };
+    let spans = feed_script_with_spans(&mut lexer, script)?;
+    let tokens = lexer.kinds.iter().zip(spans)
+        .map(|(&kind, span)| BorrowedToken { kind, text: &script[span] })
+        .collect();
+
+    Ok(tokens)
+}
+
+pub fn tokenize(script: &[u8]) -> Result<Vec<Token>, Error> {
+    tokenize_with_options(script, &LexerOptions::default())
+}
+
+pub fn tokenize_with_options(script: &[u8], options: &LexerOptions) -> Result<Vec<Token>, Error> {
+    let mut lexer = Lexer { options: options.clone(), ..Lexer::new() };
     lexer.feed_script(script)?;
-    lexer.feed_eof(script)?;
+    lexer.feed_eof()?;
     Ok(take(&mut lexer.tokens))
 }
 
+/// Turns tokens back into source text that lexes to the same tokens.
+///
+/// The output is canonical rather than faithful: numbers keep their base
+/// and digits, but spacing, escapes and letter case are normalized, with a
+/// line break after each `;`.
+pub fn render(tokens: &[Token]) -> Vec<u8> {
+    let mut source = Vec::new();
+    let mut previous: Option<TokenKind> = None;
+    for token in tokens {
+        let kind = token.kind();
+        if kind == TokenKind::EOF {
+            continue;
+        }
+        match previous {
+            Some(TokenKind::Semicolon) => source.push(b'\n'),
+            Some(previous) if needs_space(previous, kind) => source.push(b' '),
+            _ => {},
+        }
+        render_token(token, &mut source);
+        previous = Some(kind);
+    }
+    source
+}
+
+fn needs_space(previous: TokenKind, next: TokenKind) -> bool {
+    let is_call_or_index = matches!(previous,
+            TokenKind::Identifier
+            | TokenKind::RightParenthesis
+            | TokenKind::RightBracket)
+        && matches!(next, TokenKind::LeftParenthesis | TokenKind::LeftBracket);
+    !is_call_or_index
+        && !matches!(previous, TokenKind::LeftParenthesis | TokenKind::LeftBracket)
+        && !matches!(next,
+            TokenKind::RightParenthesis
+            | TokenKind::RightBracket
+            | TokenKind::Comma
+            | TokenKind::Semicolon)
+}
+
+fn render_token(token: &Token, source: &mut Vec<u8>) {
+    match token {
+        Token::Identifier(name) | Token::Extension(name) => {
+            source.extend_from_slice(name);
+        },
+        Token::Integer(integer) => {
+            let (prefix, digits): (&[u8], _) = match integer.as_ref() {
+                IntegerRepresentation::Decimal(digits)     => (b"", digits),
+                IntegerRepresentation::Hexadecimal(digits) => (b"0x", digits),
+                IntegerRepresentation::Octal(digits)       => (b"0o", digits),
+                IntegerRepresentation::Binary(digits)      => (b"0b", digits),
+            };
+            source.extend_from_slice(prefix);
+            render_digits(digits, source);
+        },
+        Token::Float(float) => {
+            match float.as_ref() {
+                FloatRepresentation::Decimal { integer, fractional } => {
+                    render_digits(integer, source);
+                    source.push(b'.');
+                    render_digits(fractional, source);
+                },
+                FloatRepresentation::Scientific { integer, fractional, exponent_sign, exponent } => {
+                    render_digits(integer, source);
+                    source.push(b'.');
+                    render_digits(fractional, source);
+                    source.push(b'e');
+                    if *exponent_sign == Sign::Negative {
+                        source.push(b'-');
+                    }
+                    render_digits(exponent, source);
+                },
+            }
+        },
+        Token::Character(character) => {
+            source.push(b'\'');
+            let mut buffer = [0; 4];
+            render_literal(character.encode_utf8(&mut buffer).as_bytes(), b'\'', source);
+            source.push(b'\'');
+        },
+        Token::String(literal) => {
+            source.push(b'"');
+            render_literal(literal, b'"', source);
+            source.push(b'"');
+        },
+        Token::InterpolatedString(segments) => {
+            source.push(b'"');
+            for segment in segments.iter() {
+                match segment {
+                    StringSegment::Literal(literal) => {
+                        render_literal(literal, b'"', source);
+                    },
+                    StringSegment::Tokens(tokens) => {
+                        source.extend_from_slice(b"${");
+                        source.extend_from_slice(&render(tokens));
+                        source.push(b'}');
+                    },
+                }
+            }
+            source.push(b'"');
+        },
+        _ => {
+            source.extend_from_slice(token.kind().spelling().unwrap().as_bytes());
+        },
+    }
+}
+
+fn render_digits(digits: &[u8], source: &mut Vec<u8>) {
+    source.extend(digits.iter().map(|&digit| b"0123456789abcdef"[digit as usize]));
+}
+
+/// Writes the contents of a character or string literal, escaping the
+/// closing `quote` and anything else that would not lex back to itself.
+fn render_literal(literal: &[u8], quote: u8, source: &mut Vec<u8>) {
+    for &byte in literal {
+        match byte {
+            b'\n' => source.extend_from_slice(b"\\n"),
+            b'\r' => source.extend_from_slice(b"\\r"),
+            b'\t' => source.extend_from_slice(b"\\t"),
+            b'\0' => source.extend_from_slice(b"\\0"),
+            b'\\' => source.extend_from_slice(b"\\\\"),
+            b'$' if quote == b'"' => source.extend_from_slice(b"\\$"),
+            _ if byte == quote => source.extend_from_slice(&[b'\\', quote]),
+            0x00..=0x1f | 0x7f => source.extend_from_slice(format!("\\x{:02x}", byte).as_bytes()),
+            _ => source.push(byte),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -615,19 +2353,43 @@ mod tests {
                 integer: vec![], fractional: vec![1, 4],
             })),
             Token::Float(Box::new(FloatRepresentation::Scientific {
-                integer: vec![3], fractional: vec![1, 4], exponent: vec![1, 0],
+                integer: vec![3], fractional: vec![1, 4],
+                exponent_sign: Sign::Positive, exponent: vec![1, 0],
+            })),
+            Token::Float(Box::new(FloatRepresentation::Scientific {
+                integer: vec![0], fractional: vec![],
+                exponent_sign: Sign::Positive, exponent: vec![1],
+            })),
+            Token::Float(Box::new(FloatRepresentation::Scientific {
+                integer: vec![3], fractional: vec![],
+                exponent_sign: Sign::Positive, exponent: vec![1, 0],
+            })),
+            Token::Float(Box::new(FloatRepresentation::Scientific {
+                integer: vec![], fractional: vec![1, 4],
+                exponent_sign: Sign::Positive, exponent: vec![1, 0],
             })),
+        ]);
+
+        tokens = tokenize(b"3.14e-10 1e+2 .5e-1").unwrap();
+        assert_eq!(tokens, vec![
             Token::Float(Box::new(FloatRepresentation::Scientific {
-                integer: vec![0], fractional: vec![], exponent: vec![1],
+                integer: vec![3], fractional: vec![1, 4],
+                exponent_sign: Sign::Negative, exponent: vec![1, 0],
             })),
             Token::Float(Box::new(FloatRepresentation::Scientific {
-                integer: vec![3], fractional: vec![], exponent: vec![1, 0],
+                integer: vec![1], fractional: vec![],
+                exponent_sign: Sign::Positive, exponent: vec![2],
             })),
             Token::Float(Box::new(FloatRepresentation::Scientific {
-                integer: vec![], fractional: vec![1, 4], exponent: vec![1, 0],
+                integer: vec![], fractional: vec![5],
+                exponent_sign: Sign::Negative, exponent: vec![1],
             })),
         ]);
 
+        assert!(matches!(tokenize(b"1e-;"), Err(Error::MissingDigitsAfterExponentMark(Position { offset: 3, .. }))));
+        assert!(matches!(tokenize(b"1e;"), Err(Error::MissingDigitsAfterExponentMark(Position { offset: 2, .. }))));
+        assert!(matches!(tokenize(b"1e+"), Err(Error::MissingDigitsAfterExponentMark(Position { offset: 3, .. }))));
+
         tokens = tokenize(b"0x64 0o77 0b10100101").unwrap();
         assert_eq!(tokens, vec![
             Token::Integer(Box::new(IntegerRepresentation::Hexadecimal(vec![6, 4]))),
@@ -643,5 +2405,513 @@ mod tests {
             Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![1, 2, 3]))),
             Token::Semicolon,
         ]);
+
+        tokens = tokenize(b"a == b != c < d <= e > f >= g -> h").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Identifier(Box::new(b"a".to_vec())),
+            Token::Equals,
+            Token::Identifier(Box::new(b"b".to_vec())),
+            Token::NotEquals,
+            Token::Identifier(Box::new(b"c".to_vec())),
+            Token::LessThan,
+            Token::Identifier(Box::new(b"d".to_vec())),
+            Token::LessThanOrEquals,
+            Token::Identifier(Box::new(b"e".to_vec())),
+            Token::GreaterThan,
+            Token::Identifier(Box::new(b"f".to_vec())),
+            Token::GreaterThanOrEquals,
+            Token::Identifier(Box::new(b"g".to_vec())),
+            Token::RightArrow,
+            Token::Identifier(Box::new(b"h".to_vec())),
+        ]);
+
+        tokens = tokenize(b"1<2>=3").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![1]))),
+            Token::LessThan,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![2]))),
+            Token::GreaterThanOrEquals,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![3]))),
+        ]);
+
+        assert!(matches!(tokenize(b"a ! b"), Err(Error::UnexpectedByte(Position { offset: 3, .. }))));
+
+        tokens = tokenize(b"a & b | c ^ ~d << 1 >> 2").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Identifier(Box::new(b"a".to_vec())),
+            Token::Ampersand,
+            Token::Identifier(Box::new(b"b".to_vec())),
+            Token::VerticalBar,
+            Token::Identifier(Box::new(b"c".to_vec())),
+            Token::Caret,
+            Token::Tilde,
+            Token::Identifier(Box::new(b"d".to_vec())),
+            Token::LeftShift,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![1]))),
+            Token::RightShift,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![2]))),
+        ]);
+
+        tokens = tokenize("'a' '\\n' '\\'' '\\u{1F600}' '√'".as_bytes()).unwrap();
+        assert_eq!(tokens, vec![
+            Token::Character('a'),
+            Token::Character('\n'),
+            Token::Character('\''),
+            Token::Character('\u{1F600}'),
+            Token::Character('√'),
+        ]);
+
+        assert!(matches!(tokenize(b"'ab'"), Err(Error::InvalidCharacterLiteral(Position { offset: 3, .. }))));
+        assert!(matches!(tokenize(b"''"), Err(Error::InvalidCharacterLiteral(Position { offset: 1, .. }))));
+        assert!(matches!(tokenize(b"'\\q'"), Err(Error::InvalidEscapeSequence(Position { offset: 2, .. }))));
+        assert!(matches!(tokenize(b"'\\u{110000}'"), Err(Error::InvalidEscapeSequence(Position { offset: 10, .. }))));
+        assert!(matches!(tokenize(b"'a"), Err(Error::UnterminatedCharacterLiteral(Position { offset: 2, .. }))));
+
+        tokens = tokenize(b"\"\" \"a\\tb\\n\" \"\\\\\\\"\\0\" \"\\x41\\xff\" \"\\u{e9}\"").unwrap();
+        assert_eq!(tokens, vec![
+            Token::String(Box::new(b"".to_vec())),
+            Token::String(Box::new(b"a\tb\n".to_vec())),
+            Token::String(Box::new(b"\\\"\0".to_vec())),
+            Token::String(Box::new(b"A\xff".to_vec())),
+            Token::String(Box::new("\u{e9}".as_bytes().to_vec())),
+        ]);
+
+        tokens = tokenize(b"\"hello ${name}!\" \"${a + f(\"}\")}${ {b} }\" \"$ \\${x}\"").unwrap();
+        assert_eq!(tokens, vec![
+            Token::InterpolatedString(Box::new(vec![
+                StringSegment::Literal(b"hello ".to_vec()),
+                StringSegment::Tokens(vec![Token::Identifier(Box::new(b"name".to_vec()))]),
+                StringSegment::Literal(b"!".to_vec()),
+            ])),
+            Token::InterpolatedString(Box::new(vec![
+                StringSegment::Tokens(vec![
+                    Token::Identifier(Box::new(b"a".to_vec())),
+                    Token::Plus,
+                    Token::Identifier(Box::new(b"f".to_vec())),
+                    Token::LeftParenthesis,
+                    Token::String(Box::new(b"}".to_vec())),
+                    Token::RightParenthesis,
+                ]),
+                StringSegment::Tokens(vec![
+                    Token::LeftBrace,
+                    Token::Identifier(Box::new(b"b".to_vec())),
+                    Token::RightBrace,
+                ]),
+            ])),
+            Token::String(Box::new(b"$ ${x}".to_vec())),
+        ]);
+
+        assert!(matches!(tokenize(b"\"${1 @}\""), Err(Error::UnexpectedByte(Position { offset: 5, .. }))));
+        assert!(matches!(tokenize(b"\"${0x}\""), Err(Error::MissingDigitsAfterBasePrefix(Position { offset: 5, .. }))));
+        assert!(matches!(tokenize(b"\"${x"), Err(Error::UnterminatedStringLiteral(Position { offset: 4, .. }))));
+
+        assert!(matches!(tokenize(b"\"a\\qb\""), Err(Error::InvalidEscapeSequence(Position { offset: 3, .. }))));
+        assert!(matches!(tokenize(b"\"\\x4\""), Err(Error::InvalidEscapeSequence(Position { offset: 4, .. }))));
+        assert!(matches!(tokenize(b"\"abc"), Err(Error::UnterminatedStringLiteral(Position { offset: 4, .. }))));
+        assert!(matches!(tokenize(b"\"abc\\"), Err(Error::UnterminatedStringLiteral(Position { offset: 5, .. }))));
+
+        tokens = tokenize(b"a |> b | c|").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Identifier(Box::new(b"a".to_vec())),
+            Token::Pipeline,
+            Token::Identifier(Box::new(b"b".to_vec())),
+            Token::VerticalBar,
+            Token::Identifier(Box::new(b"c".to_vec())),
+            Token::VerticalBar,
+        ]);
+
+        tokens = tokenize(b"a += 1; b -= 2; c *= 3; d /= 4; e+f*g/h").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Identifier(Box::new(b"a".to_vec())),
+            Token::PlusAssign,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![1]))),
+            Token::Semicolon,
+            Token::Identifier(Box::new(b"b".to_vec())),
+            Token::MinusAssign,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![2]))),
+            Token::Semicolon,
+            Token::Identifier(Box::new(b"c".to_vec())),
+            Token::AsteriskAssign,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![3]))),
+            Token::Semicolon,
+            Token::Identifier(Box::new(b"d".to_vec())),
+            Token::ForwardSlashAssign,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![4]))),
+            Token::Semicolon,
+            Token::Identifier(Box::new(b"e".to_vec())),
+            Token::Plus,
+            Token::Identifier(Box::new(b"f".to_vec())),
+            Token::Asterisk,
+            Token::Identifier(Box::new(b"g".to_vec())),
+            Token::ForwardSlash,
+            Token::Identifier(Box::new(b"h".to_vec())),
+        ]);
+    }
+
+    #[test]
+    fn keywords() {
+        let tokens = tokenize(b"while for in break continue match struct enum const import then whiles _for").unwrap();
+        assert_eq!(tokens, vec![
+            Token::While,
+            Token::For,
+            Token::In,
+            Token::Break,
+            Token::Continue,
+            Token::Match,
+            Token::Struct,
+            Token::Enum,
+            Token::Const,
+            Token::Import,
+            Token::Then,
+            Token::Identifier(Box::new(b"whiles".to_vec())),
+            Token::Identifier(Box::new(b"_for".to_vec())),
+        ]);
+    }
+
+    #[test]
+    fn kind() {
+        for kind in [TokenKind::Plus, TokenKind::Pipeline, TokenKind::GreaterThanOrEquals, TokenKind::Import] {
+            let tokens = tokenize(kind.spelling().unwrap().as_bytes()).unwrap();
+            assert_eq!(tokens.iter().map(Token::kind).collect::<Vec<_>>(), vec![kind]);
+        }
+        assert_eq!(TokenKind::Identifier.spelling(), None);
+        assert_eq!(TokenKind::LeftShift.to_string(), "`<<`");
+        assert_eq!(TokenKind::String.to_string(), "string literal");
+    }
+
+    #[test]
+    fn streaming() {
+        let script = "let s = \"h\\u{e9}llo ${x}\"; let n = 0x1f + 3.5e-2;".as_bytes();
+        let expected = tokenize(script).unwrap();
+        for chunk_size in 1..script.len() {
+            let mut lexer = StreamingLexer::new();
+            let mut tokens = vec![];
+            for chunk in script.chunks(chunk_size) {
+                lexer.push_bytes(chunk).unwrap();
+                tokens.append(&mut lexer.take_tokens());
+            }
+            tokens.append(&mut lexer.finish().unwrap());
+            assert_eq!(tokens, expected);
+        }
+
+        let mut lexer = StreamingLexer::new();
+        lexer.push_bytes(b"let x").unwrap();
+        assert!(matches!(lexer.push_bytes(b" = 0x;"), Err(Error::MissingDigitsAfterBasePrefix(Position { offset: 10, .. }))));
+
+        let mut lexer = StreamingLexer::new();
+        lexer.push_bytes(b"1e").unwrap();
+        assert!(matches!(lexer.finish(), Err(Error::MissingDigitsAfterExponentMark(Position { offset: 2, .. }))));
+    }
+
+    #[test]
+    fn iterator() {
+        let script = b"let x = 'a' + 0o17 * \"${y}\"";
+        let tokens: Result<Vec<Token>, Error> = TokenIterator::new(script).collect();
+        assert_eq!(tokens.unwrap(), tokenize(script).unwrap());
+
+        let mut tokens = TokenIterator::new(b"a b 0b2 c");
+        assert_eq!(tokens.next().unwrap().unwrap(), Token::Identifier(Box::new(b"a".to_vec())));
+        assert_eq!(tokens.next().unwrap().unwrap(), Token::Identifier(Box::new(b"b".to_vec())));
+        assert!(matches!(tokens.next(), Some(Err(Error::InvalidBinaryDigit(Position { offset: 6, .. })))));
+        assert!(tokens.next().is_none());
+    }
+
+    #[test]
+    fn recovery() {
+        let (tokens, errors) = tokenize_with_recovery(b"let a = 0x; let b = 12ab3 @ c;(09)\"x");
+        assert_eq!(tokens, vec![
+            Token::Let,
+            Token::Identifier(Box::new(b"a".to_vec())),
+            Token::Assign,
+            Token::Semicolon,
+            Token::Let,
+            Token::Identifier(Box::new(b"b".to_vec())),
+            Token::Assign,
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![1, 2]))),
+            Token::Identifier(Box::new(b"ab3".to_vec())),
+            Token::Identifier(Box::new(b"c".to_vec())),
+            Token::Semicolon,
+            Token::LeftParenthesis,
+            Token::RightParenthesis,
+        ]);
+        assert!(matches!(errors.as_slice(), [
+            Error::MissingDigitsAfterBasePrefix(Position { offset: 10, .. }),
+            Error::UnexpectedByte(Position { offset: 26, .. }),
+            Error::LeadingZeroWithoutBase(Position { offset: 32, .. }),
+            Error::UnterminatedStringLiteral(Position { offset: 36, .. }),
+        ]));
+
+        // An error inside a literal skips the rest of the literal.
+        let (tokens, errors) = tokenize_with_recovery(b"let s = \"a \\q b\"; let t = 1;");
+        assert_eq!(tokens, tokenize(b"let s = ; let t = 1;").unwrap());
+        assert!(matches!(errors.as_slice(), [Error::InvalidEscapeSequence(Position { offset: 12, .. })]));
+
+        let (tokens, errors) = tokenize_with_recovery(b"\"\\u{zz}\" x");
+        assert_eq!(tokens, vec![Token::Identifier(Box::new(b"x".to_vec()))]);
+        assert!(matches!(errors.as_slice(), [Error::InvalidEscapeSequence(Position { offset: 4, .. })]));
+
+        let (tokens, errors) = tokenize_with_recovery(b"\"${ \"}\\q\" + {1} } \\$\" x 'ab' 'c\nd");
+        assert_eq!(tokens, tokenize(b"x d").unwrap());
+        assert!(matches!(errors.as_slice(), [
+            Error::InvalidEscapeSequence(Position { offset: 7, .. }),
+            Error::InvalidCharacterLiteral(Position { offset: 27, .. }),
+            Error::UnterminatedCharacterLiteral(Position { offset: 31, .. }),
+        ]));
+
+        let (tokens, errors) = tokenize_with_recovery(b"let x = 1;");
+        assert_eq!(tokens, tokenize(b"let x = 1;").unwrap());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn position() {
+        let error = tokenize(b"let a = 1;\nlet b = 0x;\r\n\tc").unwrap_err();
+        assert_eq!(error.position(), Position { line: 2, column: 11, offset: 21 });
+        assert_eq!(error.to_string(), "missing digits after base prefix at line 2, column 11");
+
+        let error = tokenize(b"\n\n\"abc\ndef").unwrap_err();
+        assert_eq!(error.position(), Position { line: 4, column: 4, offset: 10 });
+
+        let (_, errors) = tokenize_with_recovery(b"@abc\n@");
+        let positions: Vec<Position> = errors.iter().map(Error::position).collect();
+        assert_eq!(positions, vec![
+            Position { line: 1, column: 1, offset: 0 },
+            Position { line: 2, column: 1, offset: 5 },
+        ]);
+
+        assert_eq!(Position::locate(b"let a = 1;\nlet b = 0x;", 21), Position { line: 2, column: 11, offset: 21 });
+        assert_eq!(Position::locate(b"ab", 2), Position { line: 1, column: 3, offset: 2 });
+    }
+
+    #[test]
+    fn render() {
+        let source: &[u8] = b"let mut x = (0x1F + 0o17) * 0b101 - .5e-3 / 3.;\nf(x, 'a', '\\'', \"\\t\\\"$\\${x}\\x7f\", \"a${g(\"}\", 1)}b\");\n";
+        let tokens = tokenize(source).unwrap();
+        let rendered = super::render(&tokens);
+        assert_eq!(rendered, b"let mut x = (0x1f + 0o17) * 0b101 - .5e-3 / 3.;\nf(x, 'a', '\\'', \"\\t\\\"\\$\\${x}\\x7f\", \"a${g(\"}\", 1)}b\");".to_vec());
+        assert_eq!(tokenize(&rendered).unwrap(), tokens);
+
+        let tokens = tokenize("'\u{e9}' \"\u{1F600}\n\" 1e10".as_bytes()).unwrap();
+        assert_eq!(tokenize(&super::render(&tokens)).unwrap(), tokens);
+    }
+
+    #[test]
+    fn trivia() {
+        let script = b"  let x = f(1);   \n\n\tx += 0x2 ;\n  ";
+        let tokens = tokenize_with_trivia(script).unwrap();
+        let mut source = vec![];
+        for token in &tokens {
+            source.extend_from_slice(&token.leading);
+            source.extend_from_slice(&token.text);
+            source.extend_from_slice(&token.trailing);
+        }
+        assert_eq!(source, script.to_vec());
+
+        assert_eq!(tokens.iter().map(|token| &token.token).collect::<Vec<_>>(),
+            tokenize(script).unwrap().iter().chain([&Token::EOF]).collect::<Vec<_>>());
+        assert_eq!(tokens[0], TriviaToken {
+            leading: b"  ".to_vec(), token: Token::Let, text: b"let".to_vec(), trailing: b" ".to_vec(),
+        });
+        assert_eq!(tokens[7].text, b";");
+        assert_eq!(tokens[7].trailing, b"   \n");
+        assert_eq!(tokens[8].leading, b"\n\t");
+        assert_eq!(tokens[10].text, b"0x2");
+        assert_eq!(tokens[10].trailing, b" ");
+        assert_eq!(tokens[11].leading, b"");
+        assert_eq!(tokens[11].trailing, b"\n");
+        assert_eq!(tokens[12], TriviaToken {
+            leading: b"  ".to_vec(), token: Token::EOF, text: vec![], trailing: vec![],
+        });
+
+        let tokens = tokenize_with_trivia(b"a+\"${b}\"").unwrap();
+        let texts: Vec<&[u8]> = tokens.iter().map(|token| token.text.as_slice()).collect();
+        assert_eq!(texts, vec![&b"a"[..], b"+", b"\"${b}\"", b""]);
+    }
+
+    #[test]
+    fn borrowed() {
+        let script = "#!/usr/bin/env bark\nlet s = \"a\\n${x[0]}\" + 0x1F * 2.5e-3 ?? 'é';".as_bytes();
+        let tokens = tokenize_borrowed(script).unwrap();
+        assert_eq!(tokens.iter().map(|token| token.kind).collect::<Vec<_>>(),
+            tokenize(script).unwrap().iter().map(Token::kind).collect::<Vec<_>>());
+        let texts: Vec<&[u8]> = tokens.iter().map(|token| token.text).collect();
+        assert_eq!(texts, vec![
+            &b"let"[..], b"s", b"=", b"\"a\\n${x[0]}\"", b"+", b"0x1F", b"*", b"2.5e-3", b"??",
+            "'é'".as_bytes(), b";",
+        ]);
+        assert_eq!(tokens[1], BorrowedToken { kind: TokenKind::Identifier, text: b"s" });
+
+        assert!(matches!(tokenize_borrowed(b"a 0 007"),
+            Err(Error::LeadingZeroWithoutBase(Position { offset: 5, .. }))));
+    }
+
+    #[test]
+    fn shebang() {
+        let tokens = tokenize(b"#!/usr/bin/env bark\nlet x = 1").unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0], Token::Let);
+        assert_eq!(tokenize(b"#!bark").unwrap(), vec![]);
+
+        let mut lexer = StreamingLexer::new();
+        lexer.push_bytes(b"#").unwrap();
+        lexer.push_bytes(b"! bark\n0").unwrap();
+        assert_eq!(lexer.finish().unwrap().len(), 1);
+
+        assert!(matches!(tokenize(b"#"), Err(Error::UnexpectedByte(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b"#x"), Err(Error::UnexpectedByte(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b"\xEF\xBB\xBF#x"), Err(Error::UnexpectedByte(Position { offset: 3, .. }))));
+        assert!(matches!(tokenize_with_trivia(b"#x"), Err(Error::UnexpectedByte(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b" #!bark"), Err(Error::UnexpectedByte(Position { offset: 1, .. }))));
+        assert!(matches!(tokenize(b"\"${#!}\""), Err(Error::UnexpectedByte(_))));
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_eq!(tokenize(b"\xEF\xBB\xBFlet").unwrap(), vec![Token::Let]);
+        assert_eq!(tokenize(b"\xEF\xBB\xBF#!/usr/bin/env bark\nlet").unwrap(), vec![Token::Let]);
+        assert_eq!(tokenize(b"\xEF\xBB\xBF").unwrap(), vec![]);
+        assert!(matches!(tokenize(b"\xFF\xFEl\0"), Err(Error::UnsupportedEncoding(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b"\xFE\xFF\0l"), Err(Error::UnsupportedEncoding(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b"\xEF\xBBx"), Err(Error::UnexpectedByte(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b"\xEFa"), Err(Error::UnexpectedByte(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b"\xEF\xBB"), Err(Error::UnexpectedByte(Position { offset: 0, .. }))));
+        assert!(matches!(tokenize(b" \xEF\xBB\xBF"), Err(Error::UnexpectedByte(Position { offset: 1, .. }))));
+    }
+
+    #[test]
+    fn options() {
+        let mut options = LexerOptions::new();
+        options.keyword("select").keyword("let");
+        let tokens = tokenize_with_options(b"select selected let \"${select}\"", &options).unwrap();
+        assert_eq!(tokens[0], Token::Extension(Box::new(b"select".to_vec())));
+        assert_eq!(tokens[1], Token::Identifier(Box::new(b"selected".to_vec())));
+        assert_eq!(tokens[2], Token::Let);
+        let Token::InterpolatedString(segments) = &tokens[3] else { panic!() };
+        assert_eq!(segments[0], StringSegment::Tokens(vec![Token::Extension(Box::new(b"select".to_vec()))]));
+        assert_eq!(super::render(&tokens), b"select selected let \"${select}\"");
+
+        assert_eq!(tokenize(b"select").unwrap(), vec![Token::Identifier(Box::new(b"select".to_vec()))]);
+
+        let mut options = LexerOptions::new();
+        options.disable_keyword("lambda").disable_keyword("match").keyword("match");
+        let tokens = tokenize_with_options(b"lambda match let", &options).unwrap();
+        assert_eq!(tokens, vec![
+            Token::Identifier(Box::new(b"lambda".to_vec())),
+            Token::Extension(Box::new(b"match".to_vec())),
+            Token::Let,
+        ]);
+
+        let mut options = LexerOptions::new();
+        options.allow_leading_zeros();
+        let tokens = tokenize_with_options(b"007 00 0 000.50", &options).unwrap();
+        assert_eq!(tokens, vec![
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![0, 0, 7]))),
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![0, 0]))),
+            Token::Integer(Box::new(IntegerRepresentation::Decimal(vec![0]))),
+            Token::Float(Box::new(FloatRepresentation::Decimal {
+                integer: vec![0, 0, 0], fractional: vec![5, 0],
+            })),
+        ]);
+        assert_eq!(super::render(&tokens), b"007 00 0 000.50");
+        assert!(matches!(tokenize(b"007"), Err(Error::LeadingZeroWithoutBase(_))));
+    }
+
+    #[test]
+    fn division() {
+        let tokens = tokenize(b"a//b / c%d //= %= /=").unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(Token::kind).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Identifier,
+            TokenKind::DoubleForwardSlash,
+            TokenKind::Identifier,
+            TokenKind::ForwardSlash,
+            TokenKind::Identifier,
+            TokenKind::Percent,
+            TokenKind::Identifier,
+            TokenKind::DoubleForwardSlashAssign,
+            TokenKind::PercentAssign,
+            TokenKind::ForwardSlashAssign,
+        ]);
+        assert_eq!(tokenize(b"1//").unwrap().last(), Some(&Token::DoubleForwardSlash));
+        assert_eq!(tokenize(b"1%").unwrap().last(), Some(&Token::Percent));
+    }
+
+    #[test]
+    fn path() {
+        let kinds: Vec<TokenKind> = tokenize(b"a::b : c:::").unwrap().iter().map(Token::kind).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Identifier,
+            TokenKind::DoubleColon,
+            TokenKind::Identifier,
+            TokenKind::Colon,
+            TokenKind::Identifier,
+            TokenKind::DoubleColon,
+            TokenKind::Colon,
+        ]);
+    }
+
+    #[test]
+    fn optional() {
+        let kinds: Vec<TokenKind> = tokenize(b"a?.b ?? c").unwrap().iter().map(Token::kind).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Identifier,
+            TokenKind::QuestionDot,
+            TokenKind::Identifier,
+            TokenKind::DoubleQuestion,
+            TokenKind::Identifier,
+        ]);
+        assert!(matches!(tokenize(b"a ? b"), Err(Error::UnexpectedByte(Position { offset: 3, .. }))));
+        assert!(matches!(tokenize(b"a?"), Err(Error::UnexpectedByte(Position { offset: 2, .. }))));
+    }
+
+    #[test]
+    fn conversion() {
+        let integer = |source: &[u8]| {
+            let Ok(tokens) = tokenize(source) else { panic!() };
+            let [Token::Integer(integer)] = tokens.as_slice() else { panic!() };
+            integer.as_ref().clone()
+        };
+        assert_eq!(integer(b"0").to_u64(), Some(0));
+        assert_eq!(integer(b"18446744073709551615").to_u64(), Some(u64::MAX));
+        assert_eq!(integer(b"18446744073709551616").to_u64(), None);
+        assert_eq!(integer(b"18446744073709551616").to_i128(), Some(1 << 64));
+        assert_eq!(integer(b"0x10000000000000000").to_u64(), None);
+        assert_eq!(integer(b"0xDEADbeef").to_u64(), Some(0xdeadbeef));
+        assert_eq!(integer(b"0o777").to_u64(), Some(0o777));
+        assert_eq!(integer(b"0b1011").to_i128(), Some(0b1011));
+        assert_eq!(integer(b"0x7fffffffffffffffffffffffffffffff").to_i128(), Some(i128::MAX));
+        assert_eq!(integer(b"0x80000000000000000000000000000000").to_i128(), None);
+        #[cfg(feature = "bigint")]
+        {
+            let value = integer(b"0x80000000000000000000000000000000").to_bigint();
+            assert_eq!(value, num_bigint::BigInt::from(i128::MAX) + 1);
+            assert_eq!(integer(b"0").to_bigint(), num_bigint::BigInt::from(0));
+        }
+
+        let float = |source: &[u8]| {
+            let Ok(tokens) = tokenize(source) else { panic!() };
+            let [Token::Float(float)] = tokens.as_slice() else { panic!() };
+            float.to_f64()
+        };
+        assert_eq!(float(b"2.75"), Some(2.75));
+        assert_eq!(float(b".5"), Some(0.5));
+        assert_eq!(float(b"2."), Some(2.0));
+        assert_eq!(float(b"1.5e3"), Some(1500.0));
+        assert_eq!(float(b"25e-4"), Some(0.0025));
+        assert_eq!(float(b"0.1e-99999999999999999999"), Some(0.0));
+        assert_eq!(float(b"1e309"), None);
+    }
+
+    #[test]
+    fn display_column() {
+        let script = "let s = \"名前e\u{301}\u{1F600}\" @".as_bytes();
+        let Err(error) = tokenize(script) else { panic!() };
+        assert_eq!(error.position().column, 25);
+        assert_eq!(error.position().display_column(script), 19);
+
+        let script = "let s = 1\n\t\"ü\" 0x;".as_bytes();
+        let Err(error) = tokenize(script) else { panic!() };
+        assert_eq!(error.position(), Position { line: 2, column: 9, offset: 18 });
+        assert_eq!(error.position().display_column(script), 8);
     }
 }
\ No newline at end of file
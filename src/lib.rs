@@ -0,0 +1,84 @@
+#![allow(clippy::needless_arbitrary_self_type, clippy::len_zero)]
+
+use std::fmt;
+use std::ops::Range;
+
+pub mod ast;
+pub mod lexer;
+pub mod parser;
+pub mod json;
+pub mod builder;
+pub mod pretty;
+pub mod quote;
+pub mod visit;
+
+pub use quote::quote;
+
+#[derive(Debug)]
+pub enum Error {
+    Lexer(lexer::Error),
+    Parser(parser::Error),
+}
+
+impl Error {
+    /// Where in the script the error is, for errors of `parse_script`.
+    pub fn position(self: &Self, script: &[u8]) -> lexer::Position {
+        match self {
+            Error::Lexer(error) => error.position(),
+            Error::Parser(error) => lexer::Position::locate(script, error.offset()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lexer(error) => write!(f, "lexical error: {}", error),
+            Error::Parser(error) => write!(f, "syntax error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(self: &Self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Lexer(error) => Some(error),
+            Error::Parser(error) => Some(error),
+        }
+    }
+}
+
+impl From<lexer::Error> for Error {
+    fn from(error: lexer::Error) -> Self {
+        Error::Lexer(error)
+    }
+}
+
+impl From<parser::Error> for Error {
+    fn from(error: parser::Error) -> Self {
+        Error::Parser(error)
+    }
+}
+
+/// Tokenizes and parses a whole script. Parser errors point at byte offsets
+/// into `script`.
+pub fn parse_script(script: &[u8]) -> Result<Vec<parser::Statement>, Error> {
+    Ok(parse_script_with_spans(script)?.0)
+}
+
+/// Like `parse_script`, also returning the byte range every statement and
+/// expression spans in `script`, as `parser::parse_program_with_spans` does.
+pub fn parse_script_with_spans(script: &[u8]) -> Result<(Vec<parser::Statement>, Vec<Range<usize>>), Error> {
+    let mut tokens = vec![];
+    let mut spans = vec![];
+    let mut offset = 0;
+    for token in lexer::tokenize_with_trivia(script)? {
+        offset += token.leading.len();
+        spans.push(offset..offset + token.text.len());
+        offset += token.text.len() + token.trailing.len();
+        if token.token.kind() != lexer::TokenKind::EOF {
+            tokens.push(token.token);
+        }
+    }
+    Ok(parser::parse_program_with_spans(&tokens, &spans, &parser::ParserOptions::new())?)
+}
@@ -1,6 +1,38 @@
-mod lexer;
-mod parser;
+use std::env;
+use std::fs;
+use std::process::ExitCode;
 
-fn main() {
+const USAGE: &str = "usage: bark ast --json <file>";
 
-}
\ No newline at end of file
+fn ast(arguments: &[String]) -> Result<(), String> {
+    let [flag, path] = arguments else {
+        return Err(USAGE.to_string());
+    };
+    if flag != "--json" {
+        return Err(USAGE.to_string());
+    }
+
+    let script = fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+    let (ast, spans) = bark::parse_script_with_spans(&script).map_err(|error| {
+        let position = error.position(&script);
+        format!("{}:{}:{}: {}", path, position.line, position.column, error)
+    })?;
+    print!("{}", bark::json::ast_to_json_with_spans(&ast, &spans));
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let result = match arguments.first().map(String::as_str) {
+        Some("ast") => ast(&arguments[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        },
+    }
+}
@@ -1,184 +1,2280 @@
-use std::mem::take;
-use crate::lexer::{Token, IntegerRepresentation, FloatRepresentation};
-use crate::parser::Error::UnexpectedToken;
+use std::fmt;
+use std::mem::{replace, take};
+use std::ops::Range;
+use crate::lexer::{Token, TokenKind, StringSegment, IntegerRepresentation, FloatRepresentation};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct UnaryOperation {
-    operand: ASTNode,
+    pub operand: Expression,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct BinaryOperation {
-    left_operand: ASTNode,
-    right_operand: ASTNode,
+    pub left_operand: Expression,
+    pub right_operand: Expression,
 }
 
-#[derive(Debug)]
-pub enum ASTNode {
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub callee: Expression,
+    pub arguments: Vec<Expression>,
+}
+
+/// Access to the field `name` of `object`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub object: Expression,
+    pub name: Vec<u8>,
+}
+
+/// `object[index]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Index {
+    pub object: Expression,
+    pub index: Expression,
+}
+
+/// `if condition then consequent else alternative`, which evaluates to one of
+/// its branches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conditional {
+    pub condition: Expression,
+    pub consequent: Expression,
+    pub alternative: Expression,
+}
+
+/// `{ statement; ...; value }`, whose value is that of its last expression,
+/// or nil if it ends with a statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+    pub value: Option<Expression>,
+}
+
+/// A type annotation, such as `int`, `geo::Point` or `[float]`. Nothing
+/// checks annotations yet; they are kept for tooling.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeExpr {
+    /// A type named by an identifier or a path.
+    Named(Vec<Vec<u8>>),
+    /// `[element]`.
+    Array(Box<TypeExpr>),
+    /// `(element, ...)`.
+    Tuple(Vec<TypeExpr>),
+}
+
+/// Writes `(a, b)`, with a trailing comma if there is a single element.
+fn write_tuple<T: fmt::Display>(f: &mut fmt::Formatter<'_>, elements: &[T]) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", element)?;
+    }
+    if elements.len() == 1 {
+        write!(f, ",")?;
+    }
+    write!(f, ")")
+}
+
+impl fmt::Display for TypeExpr {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeExpr::Named(segments) => write!(f, "{}", String::from_utf8_lossy(&segments.join(&b"::"[..]))),
+            TypeExpr::Array(element) => write!(f, "[{}]", element),
+            TypeExpr::Tuple(elements) => write_tuple(f, elements),
+        }
+    }
+}
+
+/// What a `let` binds its value to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Identifier(Vec<u8>),
+    /// `(a, b, ...)`, which destructures a tuple of as many elements.
+    Tuple(Vec<Pattern>),
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Identifier(name) => write!(f, "{}", String::from_utf8_lossy(name)),
+            Pattern::Tuple(elements) => write_tuple(f, elements),
+        }
+    }
+}
+
+/// A `let` or `let mut` binding, as in `let x: int = 3` or
+/// `let (a, b) = pair`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration {
+    pub pattern: Pattern,
+    pub mutable: bool,
+    pub annotation: Option<TypeExpr>,
+    pub value: Expression,
+}
+
+/// `function name(parameter, ...) -> type { body }`, where the return type
+/// is optional.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: Vec<u8>,
+    pub parameters: Vec<Parameter>,
+    pub return_type: Option<TypeExpr>,
+    pub body: Block,
+}
+
+/// A parameter of a function, as in `a` or `a: int`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: Vec<u8>,
+    pub annotation: Option<TypeExpr>,
+}
+
+/// Assignment of `value` to `target`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment {
+    pub target: Expression,
+    pub value: Expression,
+}
+
+/// `struct name { field, ... }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructDeclaration {
+    pub name: Vec<u8>,
+    pub fields: Vec<Vec<u8>>,
+}
+
+/// `enum name { Variant, Variant(field, ...), ... }`. Variants are built
+/// like calls, e.g. `Shape::Circle(1.5)`, or named by a path if they have
+/// no fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumDeclaration {
+    pub name: Vec<u8>,
+    pub variants: Vec<Variant>,
+}
+
+/// A variant of an enum. `Variant` and `Variant()` both have no fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variant {
+    pub name: Vec<u8>,
+    pub fields: Vec<Vec<u8>>,
+}
+
+/// `Name { field: value, ... }`, where the struct is named by a path of at
+/// least one segment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructLiteral {
+    pub name: Vec<Vec<u8>>,
+    pub fields: Vec<(Vec<u8>, Expression)>,
+}
+
+/// `for binding in iterable { body }`, running `body` once per element of
+/// `iterable` with the element bound to `binding`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct For {
+    pub binding: Vec<u8>,
+    pub iterable: Expression,
+    pub body: Block,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
     Identifier(Box<Vec<u8>>),
+    /// A name qualified by modules, e.g. `io::file::open`, with at least two
+    /// segments.
+    Path(Box<Vec<Vec<u8>>>),
     IntegerLiteral(Box<IntegerRepresentation>),
     FloatLiteral(Box<FloatRepresentation>),
+    BooleanLiteral(bool),
+    CharacterLiteral(char),
+    StringLiteral(Box<Vec<u8>>),
+    /// Concatenation of string literals and embedded expressions, in order.
+    InterpolatedString(Box<Vec<Expression>>),
+    ArrayLiteral(Box<Vec<Expression>>),
+    /// `(a, b)`, or `(a,)` with a single element, since `(a)` only groups.
+    TupleLiteral(Box<Vec<Expression>>),
+    /// `{ "key": value, other: 2 }`, where a bare name is the string key it
+    /// spells, or `{:}` when empty, since `{}` is an empty block.
+    MapLiteral(Box<Vec<(Expression, Expression)>>),
     UnaryAddition(Box<UnaryOperation>),
     UnarySubtraction(Box<UnaryOperation>),
     BinaryAddition(Box<BinaryOperation>),
     BinarySubtraction(Box<BinaryOperation>),
     BinaryMultiplication(Box<BinaryOperation>),
     BinaryDivision(Box<BinaryOperation>),
+    /// Division rounding toward negative infinity, so `-7 // 2` is `-4`.
+    BinaryFloorDivision(Box<BinaryOperation>),
+    /// Remainder of floored division, which takes the sign of the divisor:
+    /// `-7 % 2` is `1` and `7 % -2` is `-1`.
+    BinaryRemainder(Box<BinaryOperation>),
     LogicalAnd(Box<BinaryOperation>),
     LogicalOr(Box<BinaryOperation>),
     LogicalNot(Box<UnaryOperation>),
     LogicalXor(Box<BinaryOperation>),
-    Assign(Box<BinaryOperation>),
+    BitwiseAnd(Box<BinaryOperation>),
+    BitwiseOr(Box<BinaryOperation>),
+    BitwiseXor(Box<BinaryOperation>),
+    BitwiseNot(Box<UnaryOperation>),
+    BitwiseLeftShift(Box<BinaryOperation>),
+    BitwiseRightShift(Box<BinaryOperation>),
+    Call(Box<Call>),
+    Index(Box<Index>),
+    /// `object.name`.
+    MemberAccess(Box<Member>),
+    /// `object?.name`, which is nil when `object` is nil.
+    OptionalMember(Box<Member>),
+    /// `left ?? right`, which is `right` when `left` is nil.
+    NullCoalescing(Box<BinaryOperation>),
+    Equals(Box<BinaryOperation>),
+    NotEquals(Box<BinaryOperation>),
+    LessThan(Box<BinaryOperation>),
+    LessThanOrEquals(Box<BinaryOperation>),
+    GreaterThan(Box<BinaryOperation>),
+    GreaterThanOrEquals(Box<BinaryOperation>),
+    Block(Box<Block>),
+    StructLiteral(Box<StructLiteral>),
+    Conditional(Box<Conditional>),
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    Let(Box<Declaration>),
+    /// `target = value`, where the target is a variable, an index or a member
+    /// access. Compound assignments such as `x += 1` are desugared into
+    /// `x = x + 1`.
+    Assign(Box<Assignment>),
+    /// Takes no `;` after its body.
+    For(Box<For>),
+    /// Takes no `;` after its fields.
+    Struct(Box<StructDeclaration>),
+    /// Takes no `;` after its variants.
+    Enum(Box<EnumDeclaration>),
+    /// Takes no `;` after its body.
+    Function(Box<Function>),
+    /// An expression evaluated for its effects, as in `f(x);`.
+    Expression(Box<Expression>),
+}
+
+/// Every error carries the `offset` of the token it points at: its byte
+/// offset in the script if the parser was given the offsets of the tokens,
+/// as with `parse_program_with_offsets`, and its index among the parsed
+/// tokens otherwise.
 #[derive(Debug)]
 pub enum Error {
-    UnexpectedToken,
+    UnexpectedToken {
+        found: TokenKind,
+        expected: Vec<TokenKind>,
+        offset: usize,
+    },
+    /// The script ended where one of `expected` was needed.
+    UnexpectedEof {
+        expected: Vec<TokenKind>,
+        offset: usize,
+    },
+    /// The script ended before the `(`, `[` or `{` at `offset` was closed.
+    UnclosedDelimiter {
+        delimiter: TokenKind,
+        offset: usize,
+    },
+    /// `offset` points at the first token of the target.
+    InvalidAssignmentTarget {
+        offset: usize,
+    },
+}
+
+impl Error {
+    pub fn offset(self: &Self) -> usize {
+        match self {
+            Error::UnexpectedToken { offset, .. } => *offset,
+            Error::UnexpectedEof { offset, .. } => *offset,
+            Error::UnclosedDelimiter { offset, .. } => *offset,
+            Error::InvalidAssignmentTarget { offset } => *offset,
+        }
+    }
+
+    /// Moves the error to `offset`, for errors of a sub-parser whose tokens
+    /// all sit at one place of the script.
+    fn relocate(mut self: Self, to: usize) -> Self {
+        match &mut self {
+            Error::UnexpectedToken { offset, .. } => *offset = to,
+            Error::UnexpectedEof { offset, .. } => *offset = to,
+            Error::UnclosedDelimiter { offset, .. } => *offset = to,
+            Error::InvalidAssignmentTarget { offset } => *offset = to,
+        }
+        self
+    }
+}
+
+fn write_expected(f: &mut fmt::Formatter<'_>, expected: &[TokenKind]) -> fmt::Result {
+    for (i, kind) in expected.iter().enumerate() {
+        if i == 0 {
+            write!(f, ", expected {}", kind)?;
+        } else if i + 1 == expected.len() {
+            write!(f, " or {}", kind)?;
+        } else {
+            write!(f, ", {}", kind)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Error {
+    fn fmt(self: &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnexpectedToken { found, expected, offset } => {
+                write!(f, "unexpected {} at offset {}", found, offset)?;
+                write_expected(f, expected)
+            },
+            Error::UnexpectedEof { expected, offset } => {
+                write!(f, "unexpected end of input at offset {}", offset)?;
+                write_expected(f, expected)
+            },
+            Error::UnclosedDelimiter { delimiter, offset } =>
+                write!(f, "unclosed {} at offset {}", delimiter, offset),
+            Error::InvalidAssignmentTarget { offset } =>
+                write!(f, "invalid assignment target at offset {}", offset),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub(crate) type UnaryConstructor = fn(Box<UnaryOperation>) -> Expression;
+pub(crate) type BinaryConstructor = fn(Box<BinaryOperation>) -> Expression;
+
+/// Compound assignments, desugared into `Assign` of the binary operation.
+const COMPOUND_ASSIGNMENT_OPERATORS: &[(TokenKind, BinaryConstructor)] = &[
+    (TokenKind::PlusAssign, Expression::BinaryAddition),
+    (TokenKind::MinusAssign, Expression::BinarySubtraction),
+    (TokenKind::AsteriskAssign, Expression::BinaryMultiplication),
+    (TokenKind::ForwardSlashAssign, Expression::BinaryDivision),
+    (TokenKind::DoubleForwardSlashAssign, Expression::BinaryFloorDivision),
+    (TokenKind::PercentAssign, Expression::BinaryRemainder),
+];
+
+/// Prefix operators, binding tighter than every binary operator.
+const UNARY_OPERATORS: &[(TokenKind, UnaryConstructor)] = &[
+    (TokenKind::Plus, Expression::UnaryAddition),
+    (TokenKind::Minus, Expression::UnarySubtraction),
+    (TokenKind::Not, Expression::LogicalNot),
+    (TokenKind::Tilde, Expression::BitwiseNot),
+];
+
+/// Tokens that can start a primary expression.
+const PRIMARY_TOKENS: &[TokenKind] = &[
+    TokenKind::Identifier,
+    TokenKind::Integer,
+    TokenKind::Float,
+    TokenKind::True,
+    TokenKind::False,
+    TokenKind::Character,
+    TokenKind::String,
+    TokenKind::InterpolatedString,
+    TokenKind::LeftParenthesis,
+    TokenKind::LeftBracket,
+    TokenKind::LeftBrace,
+];
+
+/// The tokens that can be the key of a map entry.
+const MAP_KEY_TOKENS: &[TokenKind] = &[
+    TokenKind::Identifier,
+    TokenKind::Integer,
+    TokenKind::Float,
+    TokenKind::True,
+    TokenKind::False,
+    TokenKind::Character,
+    TokenKind::String,
+];
+
+/// How a binary operator groups with the operators of its precedence.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a < b < c` is an error rather than `(a < b) < c`.
+    NonAssociative,
+}
+
+type BinaryOperator = (TokenKind, u8, Associativity, BinaryConstructor);
+
+/// Every binary operator with its precedence, from the loosest to the
+/// tightest binding; the parser climbs this table, so an operator only needs
+/// an entry here. The one exception is `|>`, which binds looser than all of
+/// them: its right-hand side is a postfix expression rather than an operand,
+/// so `parse_pipeline` handles it on top of the table. The logical operators
+/// bind loosest, `or` below `xor` below `and`, so `a and b or c xor d` is
+/// `(a and b) or (c xor d)`. `not` is a prefix operator and binds tighter
+/// than all of them. Comparisons come next and don't chain.
+const BINARY_OPERATORS: &[BinaryOperator] = &[
+    (TokenKind::Or, 1, Associativity::Left, Expression::LogicalOr),
+    (TokenKind::Xor, 2, Associativity::Left, Expression::LogicalXor),
+    (TokenKind::And, 3, Associativity::Left, Expression::LogicalAnd),
+    (TokenKind::Equals, 4, Associativity::NonAssociative, Expression::Equals),
+    (TokenKind::NotEquals, 4, Associativity::NonAssociative, Expression::NotEquals),
+    (TokenKind::LessThan, 4, Associativity::NonAssociative, Expression::LessThan),
+    (TokenKind::LessThanOrEquals, 4, Associativity::NonAssociative, Expression::LessThanOrEquals),
+    (TokenKind::GreaterThan, 4, Associativity::NonAssociative, Expression::GreaterThan),
+    (TokenKind::GreaterThanOrEquals, 4, Associativity::NonAssociative, Expression::GreaterThanOrEquals),
+    (TokenKind::DoubleQuestion, 5, Associativity::Left, Expression::NullCoalescing),
+    (TokenKind::VerticalBar, 6, Associativity::Left, Expression::BitwiseOr),
+    (TokenKind::Caret, 7, Associativity::Left, Expression::BitwiseXor),
+    (TokenKind::Ampersand, 8, Associativity::Left, Expression::BitwiseAnd),
+    (TokenKind::LeftShift, 9, Associativity::Left, Expression::BitwiseLeftShift),
+    (TokenKind::RightShift, 9, Associativity::Left, Expression::BitwiseRightShift),
+    (TokenKind::Plus, 10, Associativity::Left, Expression::BinaryAddition),
+    (TokenKind::Minus, 10, Associativity::Left, Expression::BinarySubtraction),
+    (TokenKind::Asterisk, 11, Associativity::Left, Expression::BinaryMultiplication),
+    (TokenKind::ForwardSlash, 11, Associativity::Left, Expression::BinaryDivision),
+    (TokenKind::DoubleForwardSlash, 11, Associativity::Left, Expression::BinaryFloorDivision),
+    (TokenKind::Percent, 11, Associativity::Left, Expression::BinaryRemainder),
+];
+
+/// An operation following an operand, as chained by `parse_postfix`.
+enum Postfix {
+    Call(Vec<Expression>),
+    Index(Expression),
+    Member { name: Vec<u8>, optional: bool },
+}
+
+impl Postfix {
+    fn apply_to(self: Self, object: Expression) -> Expression {
+        match self {
+            Postfix::Call(arguments) => Expression::Call(Box::new(Call { callee: object, arguments })),
+            Postfix::Index(index) => Expression::Index(Box::new(Index { object, index })),
+            Postfix::Member { name, optional: false } => Expression::MemberAccess(Box::new(Member { object, name })),
+            Postfix::Member { name, optional: true } => Expression::OptionalMember(Box::new(Member { object, name })),
+        }
+    }
+}
+
+/// Where a node starts: the index of its first token, and where its span goes
+/// among those recorded, ahead of the spans of its children.
+#[derive(Clone, Copy)]
+struct Mark {
+    token: usize,
+    span: usize,
+}
+
+/// The precedence of the tightest binding binary operators.
+const TIGHTEST_PRECEDENCE: u8 = 11;
+
+/// Looks up the node constructor of the prefix operator spelled by `kind`.
+pub(crate) fn unary_constructor(kind: TokenKind) -> Option<UnaryConstructor> {
+    UNARY_OPERATORS.iter()
+        .find(|(operator, _)| *operator == kind)
+        .map(|(_, constructor)| *constructor)
+}
+
+/// Looks up the node constructor of the binary operator spelled by `kind`.
+pub(crate) fn binary_constructor(kind: TokenKind) -> Option<BinaryConstructor> {
+    BINARY_OPERATORS.iter()
+        .find(|(operator, ..)| *operator == kind)
+        .map(|(.., constructor)| *constructor)
+}
+
+/// Syntax an embedder adds on top of the built-in grammar, to go with the
+/// keywords reserved through `LexerOptions`.
+#[derive(Clone, Debug, Default)]
+pub struct ParserOptions {
+    prefix_operators: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+static DEFAULT_OPTIONS: ParserOptions = ParserOptions {
+    prefix_operators: Vec::new(),
+};
+
+impl ParserOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the extension keyword `keyword` a prefix operator, binding as
+    /// tightly as `~`. `keyword operand` is desugared into `function(operand)`.
+    pub fn prefix_operator(self: &mut Self, keyword: &str, function: &str) -> &mut Self {
+        self.prefix_operators.push((keyword.as_bytes().to_vec(), function.as_bytes().to_vec()));
+        self
+    }
+
+    fn prefix_function(self: &Self, keyword: &[u8]) -> Option<&[u8]> {
+        self.prefix_operators.iter()
+            .find(|(operator, _)| operator == keyword)
+            .map(|(_, function)| function.as_slice())
+    }
 }
 
 pub struct Parser<'a> {
     tokens: &'a [Token],
+    /// The byte offset of every token, or nothing to report token indices.
+    offsets: &'a [usize],
+    /// The byte offset every token ends at, or nothing to have tokens end
+    /// where the next one starts.
+    ends: &'a [usize],
     eof_token: Token,
     length: usize,
     offset: usize,
+    expected: Vec<TokenKind>,
+    options: &'a ParserOptions,
+    /// The errors recovered from so far, if the parser recovers from them.
+    errors: Option<Vec<Error>>,
+    /// Whether `Name {` starts a struct literal, which it doesn't right
+    /// before a `{` that starts a body, e.g. in `for x in xs { ... }`.
+    struct_literals: bool,
+    /// The span of every statement and expression parsed so far, in the
+    /// order `visit::Visit` walks them.
+    spans: Vec<Range<usize>>,
 }
 
 impl<'a> Parser<'a> {
-    fn new(tokens: &'a [Token]) -> Self {
+    fn new(tokens: &'a [Token], offsets: &'a [usize], options: &'a ParserOptions) -> Self {
         Self {
             tokens,
+            offsets,
+            ends: &[],
             eof_token: Token::EOF,
             length: tokens.len(),
             offset: 0,
+            expected: vec![],
+            options,
+            errors: None,
+            struct_literals: true,
+            spans: vec![],
         }
     }
 
     fn peek(self: &Self) -> &Token {
-        self.tokens.get(self.offset).unwrap_or(&self.eof_token)
+        self.lookahead(0)
+    }
+
+    /// The token `distance` tokens after the next one.
+    fn lookahead(self: &Self, distance: usize) -> &Token {
+        self.tokens.get(self.offset + distance).unwrap_or(&self.eof_token)
     }
 
     fn advance(self: &mut Self) {
         if self.offset != self.length {
             self.offset += 1;
         }
+        self.expected.clear();
     }
 
-    fn consume(self: &mut Self) -> &Token {
-        if let Some(token) = self.tokens.get(self.offset) {
-            self.offset += 1;
-            token
+    /// Records that any of `kinds` would have been accepted at the current
+    /// position, so a later failure here can report them.
+    fn note_expected(self: &mut Self, kinds: &[TokenKind]) {
+        for kind in kinds {
+            if !self.expected.contains(kind) {
+                self.expected.push(*kind);
+            }
+        }
+    }
+
+    /// Checks whether the next token is of `kind`, noting it as expected
+    /// otherwise.
+    fn check(self: &mut Self, kind: TokenKind) -> bool {
+        if self.peek().kind() == kind {
+            true
         } else {
-            &self.eof_token
+            self.note_expected(&[kind]);
+            false
         }
     }
 
-    fn expect(self: &Self, token: Token) {
+    /// Where the token at `index` sits, as reported in errors. The end of the
+    /// script sits past the last token.
+    fn offset_of(self: &Self, index: usize) -> usize {
+        match self.offsets.get(index.min(self.offsets.len().saturating_sub(1))) {
+            Some(offset) => *offset,
+            None => index,
+        }
+    }
 
+    /// Where the token at `index` ends, in the units of `offset_of`.
+    fn end_of(self: &Self, index: usize) -> usize {
+        match self.ends.get(index) {
+            Some(end) => *end,
+            None => self.offset_of(index + 1),
+        }
     }
 
-    fn parse(self: &mut Self) -> Result<ASTNode, Error> {
-        match self.consume() {
-            Token::Let => {
-                match self.consume() {
-                    Token::Identifier(identifier) => {
-                        let identifier = ASTNode::Identifier(identifier.clone());
-                        match self.consume() {
-                            Token::Assign => {
-                                let right_operand = self.parse_expression()?;
-                                Ok(ASTNode::Assign(Box::new(BinaryOperation {
-                                    left_operand: identifier, right_operand,
-                                })))
-                            },
-                            _ => Err(UnexpectedToken),
-                        }
-                    },
-                    _ => Err(UnexpectedToken),
-                }
-            },
-            _ => Err(UnexpectedToken),
+    fn mark(self: &Self) -> Mark {
+        Mark { token: self.offset, span: self.spans.len() }
+    }
+
+    /// The span from the first token of `mark` to the last token consumed.
+    fn span(self: &Self, mark: Mark) -> Range<usize> {
+        self.offset_of(mark.token)..self.end_of(self.offset - 1)
+    }
+
+    /// Records the span of the node started at `mark`, which has just been
+    /// parsed, ahead of those of its children.
+    fn record(self: &mut Self, mark: Mark) {
+        let span = self.span(mark);
+        self.spans.insert(mark.span, span);
+    }
+
+    fn unexpected_token(self: &mut Self) -> Error {
+        let expected = take(&mut self.expected);
+        let offset = self.offset_of(self.offset);
+        match self.peek().kind() {
+            TokenKind::EOF => Error::UnexpectedEof { expected, offset },
+            found => Error::UnexpectedToken { found, expected, offset },
         }
     }
 
-    fn parse_expression(self: &mut Self) -> Result<ASTNode, Error> {
-        self.parse_term()
+    /// Consumes the opening delimiter at the current position and parses what
+    /// follows with `parse`, which consumes the closing one. Reaching the end
+    /// of the script in between is reported as the delimiter left unclosed.
+    fn delimited<T>(self: &mut Self, parse: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        let opening = self.offset;
+        self.advance();
+        let struct_literals = replace(&mut self.struct_literals, true);
+        let result = parse(self);
+        self.struct_literals = struct_literals;
+        result.map_err(|error| match error {
+            Error::UnexpectedEof { .. } => Error::UnclosedDelimiter {
+                delimiter: self.tokens[opening].kind(),
+                offset: self.offset_of(opening),
+            },
+            error => error,
+        })
     }
 
-    fn parse_term(self: &mut Self) -> Result<ASTNode, Error> {
-        let mut operand = self.parse_factor()?;
+    /// Consumes the next token if it is of `kind`, and fails with every
+    /// token that would have been accepted here otherwise.
+    fn expect(self: &mut Self, kind: TokenKind) -> Result<(), Error> {
+        if !self.check(kind) {
+            return Err(self.unexpected_token());
+        }
+        self.advance();
+        Ok(())
+    }
+
+    /// Records `error` and skips to the next `;` or `}` outside of any group
+    /// opened since, if the parser recovers from errors. Gives `error` back
+    /// otherwise.
+    fn recover(self: &mut Self, error: Error) -> Result<(), Error> {
+        let Some(errors) = &mut self.errors else {
+            return Err(error);
+        };
+        errors.push(error);
+        let mut depth = 0usize;
         loop {
-            match self.peek() {
-                Token::Plus => {
-                    self.consume();
-                    let right_operand = self.parse_factor()?;
-                    operand = ASTNode::BinaryAddition(Box::new(BinaryOperation {
-                        left_operand: operand, right_operand,
-                    }));
+            match self.peek().kind() {
+                TokenKind::EOF => break,
+                TokenKind::Semicolon | TokenKind::RightBrace if depth == 0 => break,
+                TokenKind::LeftParenthesis | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightParenthesis | TokenKind::RightBracket | TokenKind::RightBrace => {
+                    depth = depth.saturating_sub(1);
                 },
-                Token::Minus => {
-                    self.consume();
-                    let right_operand = self.parse_factor()?;
-                    operand = ASTNode::BinarySubtraction(Box::new(BinaryOperation {
-                        left_operand: operand, right_operand,
-                    }));
+                _ => {},
+            }
+            self.advance();
+        }
+        Ok(())
+    }
+
+    /// Parses a statement along with the `;` ending it, if it takes one.
+    fn parse_statement(self: &mut Self) -> Result<Statement, Error> {
+        let start = self.mark();
+        let statement = self.parse()?;
+        if !matches!(statement, Statement::For(_) | Statement::Struct(_) | Statement::Enum(_) | Statement::Function(_)) {
+            self.expect(TokenKind::Semicolon)?;
+        }
+        self.record(start);
+        Ok(statement)
+    }
+
+    fn parse(self: &mut Self) -> Result<Statement, Error> {
+        if self.check(TokenKind::For) {
+            return self.parse_for();
+        }
+        if self.check(TokenKind::Struct) {
+            return self.parse_struct();
+        }
+        if self.check(TokenKind::Enum) {
+            return self.parse_enum();
+        }
+        if self.check(TokenKind::Function) {
+            return self.parse_function();
+        }
+        if !self.check(TokenKind::Let) {
+            let start = self.mark();
+            let expression = self.parse_expression()?;
+            return self.parse_expression_statement(expression, start);
+        }
+        self.advance();
+        let mutable = self.check(TokenKind::Mut);
+        if mutable {
+            self.advance();
+        }
+        let pattern = self.parse_pattern()?;
+        let annotation = self.parse_annotation()?;
+        self.expect(TokenKind::Assign)?;
+        let value = self.parse_expression()?;
+        Ok(Statement::Let(Box::new(Declaration { pattern, mutable, annotation, value })))
+    }
+
+    /// Parses `for binding in iterable { body }`.
+    fn parse_for(self: &mut Self) -> Result<Statement, Error> {
+        self.advance();
+        let binding = self.parse_name()?;
+        self.expect(TokenKind::In)?;
+        let struct_literals = replace(&mut self.struct_literals, false);
+        let iterable = self.parse_expression();
+        self.struct_literals = struct_literals;
+        let iterable = iterable?;
+        if !self.check(TokenKind::LeftBrace) {
+            return Err(self.unexpected_token());
+        }
+        let body = self.delimited(Self::parse_block)?;
+        Ok(Statement::For(Box::new(For { binding, iterable, body })))
+    }
+
+    /// Parses `struct name { field, ... }`.
+    fn parse_struct(self: &mut Self) -> Result<Statement, Error> {
+        self.advance();
+        let name = self.parse_name()?;
+        if !self.check(TokenKind::LeftBrace) {
+            return Err(self.unexpected_token());
+        }
+        let fields = self.delimited(|parser| {
+            let mut fields = vec![];
+            parser.parse_list(TokenKind::RightBrace, |parser| {
+                fields.push(parser.parse_name()?);
+                Ok(())
+            })?;
+            Ok(fields)
+        })?;
+        Ok(Statement::Struct(Box::new(StructDeclaration { name, fields })))
+    }
+
+    /// Parses `enum name { Variant, Variant(field, ...), ... }`.
+    fn parse_enum(self: &mut Self) -> Result<Statement, Error> {
+        self.advance();
+        let name = self.parse_name()?;
+        if !self.check(TokenKind::LeftBrace) {
+            return Err(self.unexpected_token());
+        }
+        let variants = self.delimited(|parser| {
+            let mut variants = vec![];
+            parser.parse_list(TokenKind::RightBrace, |parser| {
+                let name = parser.parse_name()?;
+                let mut fields = vec![];
+                if parser.check(TokenKind::LeftParenthesis) {
+                    parser.delimited(|parser| parser.parse_list(TokenKind::RightParenthesis, |parser| {
+                        fields.push(parser.parse_name()?);
+                        Ok(())
+                    }))?;
+                }
+                variants.push(Variant { name, fields });
+                Ok(())
+            })?;
+            Ok(variants)
+        })?;
+        Ok(Statement::Enum(Box::new(EnumDeclaration { name, variants })))
+    }
+
+    /// Parses `function name(parameter: type, ...) -> type { body }`.
+    fn parse_function(self: &mut Self) -> Result<Statement, Error> {
+        self.advance();
+        let name = self.parse_name()?;
+        if !self.check(TokenKind::LeftParenthesis) {
+            return Err(self.unexpected_token());
+        }
+        let mut parameters = vec![];
+        self.delimited(|parser| parser.parse_list(TokenKind::RightParenthesis, |parser| {
+            let name = parser.parse_name()?;
+            let annotation = parser.parse_annotation()?;
+            parameters.push(Parameter { name, annotation });
+            Ok(())
+        }))?;
+        let mut return_type = None;
+        if self.check(TokenKind::RightArrow) {
+            self.advance();
+            return_type = Some(self.parse_type()?);
+        }
+        if !self.check(TokenKind::LeftBrace) {
+            return Err(self.unexpected_token());
+        }
+        let body = self.delimited(Self::parse_block)?;
+        Ok(Statement::Function(Box::new(Function { name, parameters, return_type, body })))
+    }
+
+    /// Parses a name, or a tuple of patterns.
+    fn parse_pattern(self: &mut Self) -> Result<Pattern, Error> {
+        if !self.check(TokenKind::LeftParenthesis) {
+            return Ok(Pattern::Identifier(self.parse_name()?));
+        }
+        let (mut elements, tuple) = self.delimited(|parser| parser.parse_tuple(Self::parse_pattern))?;
+        if tuple {
+            Ok(Pattern::Tuple(elements))
+        } else {
+            Ok(elements.pop().unwrap())
+        }
+    }
+
+    /// Parses the `: type` annotating a binding, if there is one.
+    fn parse_annotation(self: &mut Self) -> Result<Option<TypeExpr>, Error> {
+        if !self.check(TokenKind::Colon) {
+            return Ok(None);
+        }
+        self.advance();
+        Ok(Some(self.parse_type()?))
+    }
+
+    /// Parses a type: a name, a path, `[element]` or a tuple of types.
+    fn parse_type(self: &mut Self) -> Result<TypeExpr, Error> {
+        if self.check(TokenKind::LeftParenthesis) {
+            let (mut elements, tuple) = self.delimited(|parser| parser.parse_tuple(Self::parse_type))?;
+            if tuple {
+                return Ok(TypeExpr::Tuple(elements));
+            }
+            return Ok(elements.pop().unwrap());
+        }
+        if self.check(TokenKind::LeftBracket) {
+            return self.delimited(|parser| {
+                let element = parser.parse_type()?;
+                parser.expect(TokenKind::RightBracket)?;
+                Ok(TypeExpr::Array(Box::new(element)))
+            });
+        }
+        Ok(TypeExpr::Named(self.parse_segments()?))
+    }
+
+    /// Parses names separated by `::`.
+    fn parse_segments(self: &mut Self) -> Result<Vec<Vec<u8>>, Error> {
+        let mut segments = vec![self.parse_name()?];
+        while self.check(TokenKind::DoubleColon) {
+            self.advance();
+            segments.push(self.parse_name()?);
+        }
+        Ok(segments)
+    }
+
+    /// Parses an identifier, returning its name.
+    fn parse_name(self: &mut Self) -> Result<Vec<u8>, Error> {
+        self.note_expected(&[TokenKind::Identifier]);
+        let Token::Identifier(name) = self.peek() else {
+            return Err(self.unexpected_token());
+        };
+        let name = name.to_vec();
+        self.advance();
+        Ok(name)
+    }
+
+    /// Parses the rest of a statement starting with `expression`, which is
+    /// either an expression statement, ending right there, or an assignment
+    /// to `expression`. `start` marks its first token.
+    fn parse_expression_statement(self: &mut Self, expression: Expression, start: Mark) -> Result<Statement, Error> {
+        if self.check(TokenKind::Semicolon) {
+            return Ok(Statement::Expression(Box::new(expression)));
+        }
+        self.parse_assignment(expression, start)
+    }
+
+    /// Parses the rest of an assignment to `target`, starting at its `=` or
+    /// compound operator. Variables, indexing and member access can be
+    /// assigned to. Compound assignments only take variables, since their
+    /// desugaring would evaluate any other target twice. `start` marks the
+    /// first token of the target.
+    fn parse_assignment(self: &mut Self, target: Expression, start: Mark) -> Result<Statement, Error> {
+        let invalid_target = Error::InvalidAssignmentTarget { offset: self.offset_of(start.token) };
+        if self.check(TokenKind::Assign) {
+            if !matches!(target, Expression::Identifier(_) | Expression::Index(_) | Expression::MemberAccess(_)) {
+                return Err(invalid_target);
+            }
+            self.advance();
+            let value = self.parse_expression()?;
+            return Ok(Statement::Assign(Box::new(Assignment { target, value })));
+        }
+        let Some(constructor) = self.match_binary_operator(COMPOUND_ASSIGNMENT_OPERATORS) else {
+            return Err(self.unexpected_token());
+        };
+        let Expression::Identifier(identifier) = &target else {
+            return Err(invalid_target);
+        };
+        let identifier = identifier.clone();
+        self.advance();
+        let value_start = self.spans.len();
+        let right_operand = self.parse_expression()?;
+        // The operation spans the whole assignment, and its left operand is
+        // the target again.
+        let span = self.span(start);
+        let target_span = self.spans[start.span].clone();
+        self.spans.splice(value_start..value_start, [span, target_span]);
+        let value = constructor(Box::new(BinaryOperation {
+            left_operand: Expression::Identifier(identifier), right_operand,
+        }));
+        Ok(Statement::Assign(Box::new(Assignment { target, value })))
+    }
+
+    /// Parses statements, each ending with `;`, up to the end of input.
+    fn parse_program(self: &mut Self) -> Result<Vec<Statement>, Error> {
+        let mut statements = vec![];
+        while !self.check(TokenKind::EOF) {
+            let spans = self.spans.len();
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.spans.truncate(spans);
+                    self.recover(error)?;
+                    self.advance();
                 },
-                _ => break,
             }
         }
 
+        Ok(statements)
+    }
+
+    fn parse_expression(self: &mut Self) -> Result<Expression, Error> {
+        if self.check(TokenKind::If) {
+            return self.parse_conditional();
+        }
+        self.parse_pipeline()
+    }
+
+    /// Parses `if condition then consequent else alternative`. It binds more
+    /// loosely than any operator: the alternative extends as far as an
+    /// expression can, and a conditional must be grouped to be an operand.
+    fn parse_conditional(self: &mut Self) -> Result<Expression, Error> {
+        let start = self.mark();
+        self.advance();
+        let condition = self.parse_expression()?;
+        self.expect(TokenKind::Then)?;
+        let consequent = self.parse_expression()?;
+        self.expect(TokenKind::Else)?;
+        let alternative = self.parse_expression()?;
+        self.record(start);
+        Ok(Expression::Conditional(Box::new(Conditional { condition, consequent, alternative })))
+    }
+
+    /// Parses `x |> f |> g(1)`, desugaring it into `g(f(x), 1)`. The piped
+    /// value takes every operator to its left, so `a + b |> f` is `f(a + b)`.
+    /// A stage is a postfix expression whose last call, if it ends with one,
+    /// gets the piped value as its first argument: `x |> o.f(1)(2)` is
+    /// `o.f(1)(x, 2)`. The call made of a stage can be the left operand of
+    /// further operators, as in `x |> f == y`.
+    fn parse_pipeline(self: &mut Self) -> Result<Expression, Error> {
+        let start = self.mark();
+        let (mut operand, _) = self.parse_binary(0)?;
+        while self.check(TokenKind::Pipeline) {
+            self.advance();
+            let stage = self.mark();
+            let mut callee = self.parse_primary()?;
+            let mut last_call = None;
+            loop {
+                let arguments_start = self.spans.len();
+                let Some(postfix) = self.parse_postfix_operation()? else { break };
+                self.record(stage);
+                if let Some((arguments, _)) = last_call.take() {
+                    callee = Postfix::Call(arguments).apply_to(callee);
+                }
+                match postfix {
+                    Postfix::Call(arguments) => last_call = Some((arguments, arguments_start)),
+                    postfix => callee = postfix.apply_to(callee),
+                }
+            }
+            // The last call of the stage is made by the pipeline instead, and
+            // the callee comes before the piped value among its children.
+            let (mut arguments, arguments_start) = match last_call {
+                Some(last_call) => {
+                    self.spans.remove(stage.span);
+                    last_call
+                },
+                None => (vec![], self.spans.len()),
+            };
+            self.spans[start.span..arguments_start].rotate_left(stage.span - start.span);
+            arguments.insert(0, operand);
+            let call = Expression::Call(Box::new(Call { callee, arguments }));
+            self.record(start);
+            (operand, _) = self.parse_binary_operations(start, call, 0)?;
+        }
+
         Ok(operand)
     }
 
-    fn parse_factor(self: &mut Self) -> Result<ASTNode, Error> {
-        let mut operand = self.parse_primary()?;
+    /// Parses a comma-separated list of expressions after its opening
+    /// bracket, up to and including the `closing` one.
+    fn parse_arguments(self: &mut Self, arguments: &mut Vec<Expression>, closing: TokenKind) -> Result<(), Error> {
+        self.parse_list(closing, |parser| {
+            arguments.push(parser.parse_expression()?);
+            Ok(())
+        })
+    }
+
+    /// Parses a comma-separated list of items with `item`, up to and
+    /// including the `closing` token. The last item can be followed by a
+    /// comma too.
+    fn parse_list(
+        self: &mut Self,
+        closing: TokenKind,
+        mut item: impl FnMut(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
         loop {
-            match self.peek() {
-                Token::Asterisk => {
-                    self.consume();
-                    let right_operand = self.parse_primary()?;
-                    operand = ASTNode::BinaryMultiplication(Box::new(BinaryOperation {
-                        left_operand: operand, right_operand,
-                    }));
-                },
-                Token::ForwardSlash => {
-                    self.consume();
-                    let right_operand = self.parse_primary()?;
-                    operand = ASTNode::BinaryDivision(Box::new(BinaryOperation {
-                        left_operand: operand, right_operand,
-                    }));
+            if self.check(closing) {
+                self.advance();
+                return Ok(());
+            }
+            item(self)?;
+            if !self.check(TokenKind::Comma) {
+                return self.expect(closing);
+            }
+            self.advance();
+        }
+    }
+
+    /// Parses the rest of `(item, ...)` after its `(`, up to and including
+    /// the `)`. Returns the items along with whether they make a tuple:
+    /// `(a)` only groups `a`, while `()` and `(a,)` are tuples.
+    fn parse_tuple<T>(
+        self: &mut Self,
+        mut item: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<(Vec<T>, bool), Error> {
+        let mut items = vec![];
+        if !self.check(TokenKind::RightParenthesis) {
+            items.push(item(self)?);
+            if !self.check(TokenKind::Comma) {
+                self.expect(TokenKind::RightParenthesis)?;
+                return Ok((items, false));
+            }
+            self.advance();
+        }
+        self.parse_list(TokenKind::RightParenthesis, |parser| {
+            items.push(item(parser)?);
+            Ok(())
+        })?;
+        Ok((items, true))
+    }
+
+    /// Matches the next token against an operator table, noting every
+    /// operator of the table as expected if none matches.
+    fn match_binary_operator(
+        self: &mut Self,
+        operators: &[(TokenKind, BinaryConstructor)],
+    ) -> Option<BinaryConstructor> {
+        let kind = self.peek().kind();
+        for (operator, constructor) in operators {
+            if *operator == kind {
+                return Some(*constructor);
+            }
+        }
+        for (operator, _) in operators {
+            self.note_expected(&[*operator]);
+        }
+        None
+    }
+
+    /// Matches the next token against the binary operators binding at least
+    /// as tightly as `precedence` and looser than `limit`, noting them all as
+    /// expected, the tightest first, if none matches.
+    fn match_infix_operator(self: &mut Self, precedence: u8, limit: u8) -> Option<BinaryOperator> {
+        let kind = self.peek().kind();
+        let operators = BINARY_OPERATORS.iter()
+            .filter(|(_, operator_precedence, ..)| (precedence..limit).contains(operator_precedence));
+        if let Some(operator) = operators.clone().find(|(operator, ..)| *operator == kind) {
+            return Some(*operator);
+        }
+        for current in (precedence..limit.min(TIGHTEST_PRECEDENCE + 1)).rev() {
+            for (operator, ..) in operators.clone().filter(|(_, operator_precedence, ..)| *operator_precedence == current) {
+                self.note_expected(&[*operator]);
+            }
+        }
+        None
+    }
+
+    /// Parses a chain of binary operations whose operators bind at least as
+    /// tightly as `precedence`, climbing `BINARY_OPERATORS`: the right operand
+    /// of an operator only takes the operators binding tighter than it.
+    ///
+    /// Also returns the limit the chain ended with. Once a non-associative
+    /// operator has been parsed, at this level or in a right operand, no
+    /// operator of its precedence or tighter may follow, so that `a < b < c`
+    /// and `a and b < c < d` are both rejected.
+    fn parse_binary(self: &mut Self, precedence: u8) -> Result<(Expression, u8), Error> {
+        let start = self.mark();
+        let operand = self.parse_unary()?;
+        self.parse_binary_operations(start, operand, precedence)
+    }
+
+    /// Parses the binary operations `operand`, started at `start`, is the left
+    /// operand of, like `parse_binary`.
+    fn parse_binary_operations(
+        self: &mut Self,
+        start: Mark,
+        mut operand: Expression,
+        precedence: u8,
+    ) -> Result<(Expression, u8), Error> {
+        let mut limit = u8::MAX;
+        while let Some((_, operator_precedence, associativity, constructor)) = self.match_infix_operator(precedence, limit) {
+            self.advance();
+            let (right_operand, right_limit) = self.parse_binary(operator_precedence + 1)?;
+            operand = constructor(Box::new(BinaryOperation {
+                left_operand: operand, right_operand,
+            }));
+            self.record(start);
+            limit = limit.min(right_limit);
+            if associativity == Associativity::NonAssociative {
+                limit = limit.min(operator_precedence);
+            }
+        }
+
+        Ok((operand, limit))
+    }
+
+    fn parse_unary(self: &mut Self) -> Result<Expression, Error> {
+        let start = self.mark();
+        for (operator, _) in UNARY_OPERATORS {
+            self.note_expected(&[*operator]);
+        }
+        if self.options.prefix_operators.len() != 0 {
+            self.note_expected(&[TokenKind::Extension]);
+        }
+        if let Some(constructor) = unary_constructor(self.peek().kind()) {
+            self.advance();
+            let operand = self.parse_unary()?;
+            self.record(start);
+            return Ok(constructor(Box::new(UnaryOperation { operand })));
+        }
+        match self.peek() {
+            Token::Extension(keyword) => {
+                let Some(function) = self.options.prefix_function(keyword) else {
+                    return Err(self.unexpected_token());
+                };
+                let callee = Expression::Identifier(Box::new(function.to_vec()));
+                self.advance();
+                // The keyword stands for the function it calls.
+                let span = self.span(start);
+                self.spans.push(span);
+                let operand = self.parse_unary()?;
+                self.record(start);
+                Ok(Expression::Call(Box::new(Call { callee, arguments: vec![operand] })))
+            },
+            _ => self.parse_postfix(),
+        }
+    }
+
+    /// Parses a primary expression followed by any calls `(...)`, indexing
+    /// `[...]` and `.name` or `?.name` accesses, which chain from left to
+    /// right.
+    fn parse_postfix(self: &mut Self) -> Result<Expression, Error> {
+        let start = self.mark();
+        let mut object = self.parse_primary()?;
+        while let Some(postfix) = self.parse_postfix_operation()? {
+            object = postfix.apply_to(object);
+            self.record(start);
+        }
+
+        Ok(object)
+    }
+
+    /// Parses the call, indexing or member access following an operand, if
+    /// there is one.
+    fn parse_postfix_operation(self: &mut Self) -> Result<Option<Postfix>, Error> {
+        let postfix = if self.check(TokenKind::LeftParenthesis) {
+            let mut arguments = vec![];
+            self.delimited(|parser| parser.parse_arguments(&mut arguments, TokenKind::RightParenthesis))?;
+            Postfix::Call(arguments)
+        } else if self.check(TokenKind::LeftBracket) {
+            let index = self.delimited(|parser| {
+                let index = parser.parse_expression()?;
+                parser.expect(TokenKind::RightBracket)?;
+                Ok(index)
+            })?;
+            Postfix::Index(index)
+        } else if self.check(TokenKind::Dot) || self.check(TokenKind::QuestionDot) {
+            let optional = self.peek().kind() == TokenKind::QuestionDot;
+            self.advance();
+            self.note_expected(&[TokenKind::Identifier]);
+            let Token::Identifier(name) = self.peek() else {
+                return Err(self.unexpected_token());
+            };
+            let name = name.to_vec();
+            self.advance();
+            Postfix::Member { name, optional }
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(postfix))
+    }
+
+    /// Parses the rest of a block after its `{`, up to and including the
+    /// closing `}`. Every statement ends with `;`, except that the block may
+    /// end with an expression, which is its value.
+    fn parse_block(self: &mut Self) -> Result<Block, Error> {
+        let mut statements = vec![];
+        let value = loop {
+            if self.check(TokenKind::RightBrace) {
+                break None;
+            }
+            let spans = self.spans.len();
+            match self.parse_block_item(&mut statements) {
+                Ok(Some(value)) => break Some(value),
+                Ok(None) => {},
+                Err(error) => {
+                    self.spans.truncate(spans);
+                    self.recover(error)?;
+                    match self.peek().kind() {
+                        TokenKind::RightBrace => break None,
+                        TokenKind::EOF => return Err(self.unexpected_token()),
+                        _ => self.advance(),
+                    }
                 },
-                _ => break,
             }
+        };
+        self.advance();
+
+        Ok(Block { statements, value })
+    }
+
+    /// Parses a statement of a block with its `;` into `statements`, or the
+    /// expression ending the block, which is returned.
+    fn parse_block_item(self: &mut Self, statements: &mut Vec<Statement>) -> Result<Option<Expression>, Error> {
+        let declaration = [TokenKind::Let, TokenKind::For, TokenKind::Struct, TokenKind::Enum, TokenKind::Function];
+        if declaration.into_iter().any(|kind| self.check(kind)) {
+            statements.push(self.parse_statement()?);
+            return Ok(None);
+        }
+        let start = self.mark();
+        let expression = self.parse_expression()?;
+        if self.check(TokenKind::RightBrace) {
+            return Ok(Some(expression));
+        }
+        let statement = self.parse_expression_statement(expression, start)?;
+        self.expect(TokenKind::Semicolon)?;
+        self.record(start);
+        statements.push(statement);
+        Ok(None)
+    }
+
+    /// Whether the `{` about to be parsed opens a map rather than a block,
+    /// which it does if it is followed by `:` or by a key and `:`.
+    fn starts_map(self: &Self) -> bool {
+        let first = self.lookahead(1).kind();
+        first == TokenKind::Colon
+            || MAP_KEY_TOKENS.contains(&first) && self.lookahead(2).kind() == TokenKind::Colon
+    }
+
+    /// Parses the rest of a map after its `{`, up to and including the `}`.
+    fn parse_map(self: &mut Self) -> Result<Vec<(Expression, Expression)>, Error> {
+        let mut entries = vec![];
+        if self.check(TokenKind::Colon) {
+            self.advance();
+            self.expect(TokenKind::RightBrace)?;
+            return Ok(entries);
         }
+        self.parse_list(TokenKind::RightBrace, |parser| {
+            let key = parser.parse_key()?;
+            parser.expect(TokenKind::Colon)?;
+            entries.push((key, parser.parse_expression()?));
+            Ok(())
+        })?;
+        Ok(entries)
+    }
 
-        Ok(operand)
+    /// Parses the key of a map entry, a bare name being a string.
+    fn parse_key(self: &mut Self) -> Result<Expression, Error> {
+        self.note_expected(MAP_KEY_TOKENS);
+        if let Token::Identifier(name) = self.peek() {
+            let key = Expression::StringLiteral(name.clone());
+            let start = self.mark();
+            self.advance();
+            self.record(start);
+            return Ok(key);
+        }
+        if !MAP_KEY_TOKENS.contains(&self.peek().kind()) {
+            return Err(self.unexpected_token());
+        }
+        self.parse_primary()
+    }
+
+    /// Parses an identifier, or a path if it is followed by `::`, either of
+    /// which can name the struct of a literal.
+    fn parse_path(self: &mut Self) -> Result<Expression, Error> {
+        let start = self.mark();
+        let mut segments = self.parse_segments()?;
+
+        if self.struct_literals && self.check(TokenKind::LeftBrace) {
+            let fields = self.delimited(|parser| {
+                let mut fields = vec![];
+                parser.parse_list(TokenKind::RightBrace, |parser| {
+                    let name = parser.parse_name()?;
+                    parser.expect(TokenKind::Colon)?;
+                    fields.push((name, parser.parse_expression()?));
+                    Ok(())
+                })?;
+                Ok(fields)
+            })?;
+            self.record(start);
+            return Ok(Expression::StructLiteral(Box::new(StructLiteral { name: segments, fields })));
+        }
+        self.record(start);
+        if segments.len() == 1 {
+            Ok(Expression::Identifier(Box::new(segments.pop().unwrap())))
+        } else {
+            Ok(Expression::Path(Box::new(segments)))
+        }
     }
 
-    fn parse_primary(self: &mut Self) -> Result<ASTNode, Error> {
-        match self.consume() {
-            Token::Identifier(name) => {
-                Ok(ASTNode::Identifier(name.clone()))
+    fn parse_primary(self: &mut Self) -> Result<Expression, Error> {
+        let start = self.mark();
+        self.note_expected(PRIMARY_TOKENS);
+        let node = match self.peek() {
+            Token::Identifier(_) => {
+                return self.parse_path();
             },
             Token::Integer(integer) => {
-                Ok(ASTNode::IntegerLiteral(integer.clone()))
+                Expression::IntegerLiteral(integer.clone())
             },
             Token::Float(float) => {
-                Ok(ASTNode::FloatLiteral(float.clone()))
+                Expression::FloatLiteral(float.clone())
+            },
+            Token::True => {
+                Expression::BooleanLiteral(true)
+            },
+            Token::False => {
+                Expression::BooleanLiteral(false)
+            },
+            Token::Character(character) => {
+                Expression::CharacterLiteral(*character)
+            },
+            Token::String(string) => {
+                Expression::StringLiteral(string.clone())
+            },
+            Token::InterpolatedString(segments) => {
+                let offset = self.offset_of(self.offset);
+                let mut parts = vec![];
+                let mut nodes = 0;
+                for segment in segments.iter() {
+                    match segment {
+                        StringSegment::Literal(literal) => {
+                            parts.push(Expression::StringLiteral(Box::new(literal.clone())));
+                            nodes += 1;
+                        },
+                        StringSegment::Tokens(tokens) => {
+                            let mut parser = Parser::new(tokens, &[], self.options);
+                            let part = parser.parse_expression()
+                                .and_then(|part| parser.expect(TokenKind::EOF).map(|_| part))
+                                .map_err(|error| error.relocate(offset))?;
+                            parts.push(part);
+                            nodes += parser.spans.len();
+                        },
+                    }
+                }
+                // Like errors, the parts point at the whole string.
+                let span = offset..self.end_of(self.offset);
+                self.spans.extend(std::iter::repeat_n(span, nodes));
+                Expression::InterpolatedString(Box::new(parts))
             },
             Token::LeftParenthesis => {
-                let node = self.parse_expression()?;
-                match self.consume() {
-                    Token::RightParenthesis => {
-                        Ok(node)
-                    },
-                    _ => Err(UnexpectedToken),
+                let (mut elements, tuple) = self.delimited(|parser| parser.parse_tuple(Self::parse_expression))?;
+                if tuple {
+                    self.record(start);
+                    return Ok(Expression::TupleLiteral(Box::new(elements)));
                 }
+                return Ok(elements.pop().unwrap());
             },
-            _ => Err(UnexpectedToken),
-        }
+            Token::LeftBracket => {
+                let mut elements = vec![];
+                self.delimited(|parser| parser.parse_arguments(&mut elements, TokenKind::RightBracket))?;
+                self.record(start);
+                return Ok(Expression::ArrayLiteral(Box::new(elements)));
+            },
+            Token::LeftBrace if self.starts_map() => {
+                let entries = self.delimited(Self::parse_map)?;
+                self.record(start);
+                return Ok(Expression::MapLiteral(Box::new(entries)));
+            },
+            Token::LeftBrace => {
+                let block = self.delimited(Self::parse_block)?;
+                self.record(start);
+                return Ok(Expression::Block(Box::new(block)));
+            },
+            _ => return Err(self.unexpected_token()),
+        };
+        self.advance();
+        self.record(start);
+        Ok(node)
     }
 }
 
-pub fn parse(tokens: &[Token]) -> Result<ASTNode, Error> {
-    let mut parser = Parser::new(tokens);
-    parser.parse()
-}
\ No newline at end of file
+pub fn parse(tokens: &[Token]) -> Result<Statement, Error> {
+    parse_with_options(tokens, &DEFAULT_OPTIONS)
+}
+
+/// Parses a script made of exactly one statement, including its `;`.
+pub fn parse_with_options(tokens: &[Token], options: &ParserOptions) -> Result<Statement, Error> {
+    let mut parser = Parser::new(tokens, &[], options);
+    let statement = parser.parse_statement()?;
+    parser.expect(TokenKind::EOF)?;
+    Ok(statement)
+}
+
+pub fn parse_program(tokens: &[Token]) -> Result<Vec<Statement>, Error> {
+    parse_program_with_options(tokens, &DEFAULT_OPTIONS)
+}
+
+pub fn parse_program_with_options(tokens: &[Token], options: &ParserOptions) -> Result<Vec<Statement>, Error> {
+    parse_program_with_offsets(tokens, &[], options)
+}
+
+/// Like `parse_program_with_options`, with errors reporting byte offsets
+/// instead of token indices. `offsets` holds the byte offset of every token,
+/// followed by the offset of the end of the script.
+pub fn parse_program_with_offsets(
+    tokens: &[Token],
+    offsets: &[usize],
+    options: &ParserOptions,
+) -> Result<Vec<Statement>, Error> {
+    let mut parser = Parser::new(tokens, offsets, options);
+    parser.parse_program()
+}
+
+/// Like `parse_program_with_offsets`, also returning the span of every
+/// statement and expression: the bytes from the start of its first token to
+/// the end of its last one, a parent before its children, in the order
+/// `visit::Visit` walks them. `spans` holds the byte range of every token,
+/// followed by the empty range at the end of the script.
+pub fn parse_program_with_spans(
+    tokens: &[Token],
+    spans: &[Range<usize>],
+    options: &ParserOptions,
+) -> Result<(Vec<Statement>, Vec<Range<usize>>), Error> {
+    let offsets: Vec<usize> = spans.iter().map(|span| span.start).collect();
+    let ends: Vec<usize> = spans.iter().map(|span| span.end).collect();
+    let mut parser = Parser::new(tokens, &offsets, options);
+    parser.ends = &ends;
+    let statements = parser.parse_program()?;
+    Ok((statements, parser.spans))
+}
+
+/// Parses a whole script, recovering from errors instead of stopping at the
+/// first one: the statement in error is dropped and parsing resumes after the
+/// next `;`, or at the `}` closing the block it is in. Returns every
+/// statement parsed and every error met, in source order. `offsets` is as in
+/// `parse_program_with_offsets`, and can be empty.
+pub fn parse_program_with_recovery(
+    tokens: &[Token],
+    offsets: &[usize],
+    options: &ParserOptions,
+) -> (Vec<Statement>, Vec<Error>) {
+    let mut parser = Parser::new(tokens, offsets, options);
+    parser.errors = Some(vec![]);
+    let statements = parser.parse_program().unwrap_or_default();
+    let mut errors = parser.errors.unwrap_or_default();
+    // An unclosed delimiter is only reported once the error inside it has
+    // made its way out.
+    errors.sort_by_key(Error::offset);
+    (statements, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{tokenize, tokenize_with_options, tokenize_with_trivia, LexerOptions};
+    use crate::visit::{walk_expression, walk_statement, Visit};
+
+    /// Tokens expected where an operand is missing.
+    fn operand_tokens() -> Vec<TokenKind> {
+        UNARY_OPERATORS.iter().map(|(operator, _)| *operator).chain(PRIMARY_TOKENS.iter().copied()).collect()
+    }
+
+    /// The tokens that can start an expression, a conditional or an operand.
+    fn expression_tokens() -> Vec<TokenKind> {
+        [vec![TokenKind::If], operand_tokens()].concat()
+    }
+
+    #[test]
+    fn test() {
+        let tokens = tokenize(b"let x = (1 + 2) * y;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        assert_eq!(declaration.pattern, Pattern::Identifier(b"x".to_vec()));
+        assert!(!declaration.mutable);
+
+        let tokens = tokenize(b"let mut x = 1;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        assert!(declaration.mutable);
+
+        let tokens = tokenize(b"let mut = 1;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, vec![TokenKind::LeftParenthesis, TokenKind::Identifier]);
+
+        let tokens = tokenize(b"1 + x;").unwrap();
+        let Ok(Statement::Expression(expression)) = parse(&tokens) else { panic!() };
+        assert!(matches!(*expression, Expression::BinaryAddition(_)));
+
+        let tokens = tokenize(b"1 + x 2;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert!(expected.contains(&TokenKind::Semicolon));
+        assert!(expected.contains(&TokenKind::Assign));
+        assert!(expected.contains(&TokenKind::PlusAssign));
+
+        let tokens = tokenize(b"let flag = true;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        assert!(matches!(declaration.value, Expression::BooleanLiteral(true)));
+
+        let tokens = tokenize(b"let c = ',';").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        assert!(matches!(declaration.value, Expression::CharacterLiteral(',')));
+
+        let tokens = tokenize(b"let s = \"a${b * 2}c\";").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::InterpolatedString(parts) = &declaration.value else { panic!() };
+        assert!(matches!(parts.as_slice(), [
+            Expression::StringLiteral(_),
+            Expression::BinaryMultiplication(_),
+            Expression::StringLiteral(_),
+        ]));
+
+        let tokens = tokenize(b"let s = \"${b c}\";").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected.last(), Some(&TokenKind::EOF));
+
+        let tokens = tokenize(b"let y = x + 1 |> f |> g(1, 2) |> h();").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Call(h) = &declaration.value else { panic!() };
+        assert!(matches!(h.callee, Expression::Identifier(_)));
+        let [Expression::Call(g)] = h.arguments.as_slice() else { panic!() };
+        let [Expression::Call(f), Expression::IntegerLiteral(_), Expression::IntegerLiteral(_)] = g.arguments.as_slice() else { panic!() };
+        assert!(matches!(f.arguments.as_slice(), [Expression::BinaryAddition(_)]));
+
+        // A stage is a postfix expression, whose last call takes the piped value.
+        let tokens = tokenize(b"let y = x |> obj?.f |> m[0].g(1)(2);").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Call(outer) = &declaration.value else { panic!() };
+        let Expression::Call(g) = &outer.callee else { panic!() };
+        assert!(matches!(g.callee, Expression::MemberAccess(_)));
+        assert!(matches!(g.arguments.as_slice(), [Expression::IntegerLiteral(_)]));
+        let [Expression::Call(f), Expression::IntegerLiteral(_)] = outer.arguments.as_slice() else { panic!() };
+        assert!(matches!(f.callee, Expression::OptionalMember(_)));
+        assert!(matches!(f.arguments.as_slice(), [Expression::Identifier(_)]));
+
+        let tokens = tokenize(b"let y = x |> (f(1)) |> f[0];").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Call(index) = &declaration.value else { panic!() };
+        assert!(matches!(index.callee, Expression::Index(_)));
+        let [Expression::Call(grouped)] = index.arguments.as_slice() else { panic!() };
+        assert!(matches!(grouped.callee, Expression::Call(_)));
+
+        // The call made of a stage is an operand.
+        let tokens = tokenize(b"let y = a + x |> f == y + 1 |> g and z;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::LogicalAnd(and) = &declaration.value else { panic!() };
+        let Expression::Call(g) = &and.left_operand else { panic!() };
+        let [Expression::Equals(equals)] = g.arguments.as_slice() else { panic!() };
+        let Expression::Call(f) = &equals.left_operand else { panic!() };
+        assert!(matches!(f.arguments.as_slice(), [Expression::BinaryAddition(_)]));
+
+        let tokens = tokenize(b"let y = x |> f(1 2);").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert!(expected.contains(&TokenKind::Comma));
+        assert!(expected.contains(&TokenKind::RightParenthesis));
+
+        let tokens = tokenize(b"x *= y + 1;").unwrap();
+        let Ok(Statement::Assign(assign)) = parse(&tokens) else { panic!() };
+        assert!(matches!(assign.target, Expression::Identifier(_)));
+        let Expression::BinaryMultiplication(multiplication) = &assign.value else { panic!() };
+        assert!(matches!(multiplication.left_operand, Expression::Identifier(_)));
+        assert!(matches!(multiplication.right_operand, Expression::BinaryAddition(_)));
+
+        let tokens = tokenize(b"let x = (1 + 2;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert!(expected.contains(&TokenKind::Asterisk));
+        assert!(expected.contains(&TokenKind::Plus));
+        assert_eq!(expected.last(), Some(&TokenKind::RightParenthesis));
+
+        let tokens = tokenize(b"let x = 1 +;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, operand_tokens());
+
+        let tokens = tokenize(b"let x = a | b ^ c & d << 1 + ~e;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::BitwiseOr(or) = &declaration.value else { panic!() };
+        let Expression::BitwiseXor(xor) = &or.right_operand else { panic!() };
+        let Expression::BitwiseAnd(and) = &xor.right_operand else { panic!() };
+        let Expression::BitwiseLeftShift(shift) = &and.right_operand else { panic!() };
+        let Expression::BinaryAddition(addition) = &shift.right_operand else { panic!() };
+        assert!(matches!(addition.right_operand, Expression::BitwiseNot(_)));
+    }
+
+    #[test]
+    fn unary() {
+        let tokens = tokenize(b"let x = -a * +2 - not ~-b;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::BinarySubtraction(subtraction) = declaration.value else { panic!() };
+        let Expression::BinaryMultiplication(multiplication) = subtraction.left_operand else { panic!() };
+        let Expression::UnarySubtraction(negation) = multiplication.left_operand else { panic!() };
+        assert!(matches!(negation.operand, Expression::Identifier(_)));
+        assert!(matches!(multiplication.right_operand, Expression::UnaryAddition(_)));
+        let Expression::LogicalNot(not) = subtraction.right_operand else { panic!() };
+        let Expression::BitwiseNot(complement) = not.operand else { panic!() };
+        assert!(matches!(complement.operand, Expression::UnarySubtraction(_)));
+
+        let tokens = tokenize(b"let x = -f(1);").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::UnarySubtraction(negation) = declaration.value else { panic!() };
+        assert!(matches!(negation.operand, Expression::Call(_)));
+    }
+
+    #[test]
+    fn logical() {
+        let tokens = tokenize(b"let x = a and b or not c xor d and e ?? f;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::LogicalOr(or) = declaration.value else { panic!() };
+        let Expression::LogicalAnd(left) = or.left_operand else { panic!() };
+        assert!(matches!(left.right_operand, Expression::Identifier(_)));
+        let Expression::LogicalXor(xor) = or.right_operand else { panic!() };
+        assert!(matches!(xor.left_operand, Expression::LogicalNot(_)));
+        let Expression::LogicalAnd(right) = xor.right_operand else { panic!() };
+        assert!(matches!(right.right_operand, Expression::NullCoalescing(_)));
+
+        let tokens = tokenize(b"let x = a or b |> f;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Call(f) = declaration.value else { panic!() };
+        assert!(matches!(f.arguments.as_slice(), [Expression::LogicalOr(_)]));
+    }
+
+    #[test]
+    fn comparison() {
+        let tokens = tokenize(b"let x = a + 1 <= b ?? c and d != e | f or g == h;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::LogicalOr(or) = declaration.value else { panic!() };
+        let Expression::LogicalAnd(and) = or.left_operand else { panic!() };
+        let Expression::LessThanOrEquals(less) = and.left_operand else { panic!() };
+        assert!(matches!(less.left_operand, Expression::BinaryAddition(_)));
+        assert!(matches!(less.right_operand, Expression::NullCoalescing(_)));
+        let Expression::NotEquals(not_equals) = and.right_operand else { panic!() };
+        assert!(matches!(not_equals.right_operand, Expression::BitwiseOr(_)));
+        assert!(matches!(or.right_operand, Expression::Equals(_)));
+
+        let tokens = tokenize(b"let x = a < b < c;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse_program(&tokens) else { panic!() };
+        assert!(!expected.contains(&TokenKind::LessThan));
+        assert!(expected.contains(&TokenKind::And));
+
+        let tokens = tokenize(b"let x = a and b < c < d;").unwrap();
+        let Err(Error::UnexpectedToken { found, offset, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!((found, offset), (TokenKind::LessThan, 8));
+
+        let tokens = tokenize(b"a or b == c != d;").unwrap();
+        let Err(Error::UnexpectedToken { found, offset, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!((found, offset), (TokenKind::NotEquals, 5));
+
+        let tokens = tokenize(b"let x = a < b and c < d or (e == f) == g;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::LogicalOr(or) = declaration.value else { panic!() };
+        assert!(matches!(or.left_operand, Expression::LogicalAnd(_)));
+        assert!(matches!(or.right_operand, Expression::Equals(_)));
+    }
+
+    #[test]
+    fn call() {
+        let tokens = tokenize(b"let y = f(1, 2 + 3) * g()(x)?.h(a::b);").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::BinaryMultiplication(multiplication) = declaration.value else { panic!() };
+        let Expression::Call(f) = multiplication.left_operand else { panic!() };
+        assert!(matches!(f.arguments.as_slice(), [Expression::IntegerLiteral(_), Expression::BinaryAddition(_)]));
+        let Expression::Call(h) = multiplication.right_operand else { panic!() };
+        assert!(matches!(h.arguments.as_slice(), [Expression::Path(_)]));
+        let Expression::OptionalMember(member) = h.callee else { panic!() };
+        let Expression::Call(outer) = member.object else { panic!() };
+        assert!(matches!(outer.arguments.as_slice(), [Expression::Identifier(_)]));
+        let Expression::Call(g) = outer.callee else { panic!() };
+        assert!(g.arguments.is_empty());
+
+        let tokens = tokenize(b"let y = f(1, g(2,),);").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Call(f) = declaration.value else { panic!() };
+        let [Expression::IntegerLiteral(_), Expression::Call(g)] = f.arguments.as_slice() else { panic!() };
+        assert_eq!(g.arguments.len(), 1);
+
+        let tokens = tokenize(b"let y = f(1,,);").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, [vec![TokenKind::RightParenthesis], expression_tokens()].concat());
+
+        let tokens = tokenize(b"let y = f(,);").unwrap();
+        let Err(Error::UnexpectedToken { found, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::Comma);
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let tokens = tokenize(concat!(
+            "struct Point { x, y, } enum Shape { Circle(radius,), Empty, }\n",
+            "function f(a: int, b,) -> (int, float,) {\n",
+            "    let (c, d,) = (a, [b, 1,],);\n",
+            "    { a: Point { x: c, y: d, }, 'k': {:}, }\n",
+            "}\n",
+        ).as_bytes()).unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::Struct(point), Statement::Enum(shape), Statement::Function(f)] = statements.as_slice() else { panic!() };
+        assert_eq!(point.fields.len(), 2);
+        assert_eq!(shape.variants[0].fields.len(), 1);
+        assert_eq!(f.parameters.len(), 2);
+        assert!(matches!(&f.return_type, Some(TypeExpr::Tuple(elements)) if elements.len() == 2));
+        let [Statement::Let(declaration)] = f.body.statements.as_slice() else { panic!() };
+        assert!(matches!(&declaration.pattern, Pattern::Tuple(elements) if elements.len() == 2));
+        let Expression::TupleLiteral(elements) = &declaration.value else { panic!() };
+        assert!(matches!(elements.as_slice(), [Expression::Identifier(_), Expression::ArrayLiteral(array)] if array.len() == 2));
+        let Some(Expression::MapLiteral(entries)) = &f.body.value else { panic!() };
+        assert!(matches!(entries.as_slice(), [(_, Expression::StructLiteral(point)), _] if point.fields.len() == 2));
+
+        // A comma without an item before it is still an error.
+        let tokens = tokenize(b"let x = [,];").unwrap();
+        let Err(Error::UnexpectedToken { found, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::Comma);
+    }
+
+    #[test]
+    fn array() {
+        let tokens = tokenize(b"let x = [1, [], [a[0]]][i + 1][j](k);").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Call(call) = declaration.value else { panic!() };
+        let Expression::Index(outer) = call.callee else { panic!() };
+        assert!(matches!(outer.index, Expression::Identifier(_)));
+        let Expression::Index(inner) = outer.object else { panic!() };
+        assert!(matches!(inner.index, Expression::BinaryAddition(_)));
+        let Expression::ArrayLiteral(elements) = inner.object else { panic!() };
+        let [Expression::IntegerLiteral(_), Expression::ArrayLiteral(empty), Expression::ArrayLiteral(nested)] = elements.as_slice() else { panic!() };
+        assert!(empty.is_empty());
+        assert!(matches!(nested.as_slice(), [Expression::Index(_)]));
+
+        let tokens = tokenize(b"let x = a[1, 2];").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected.last(), Some(&TokenKind::RightBracket));
+
+        let tokens = tokenize(b"let x = [1 2];").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert!(expected.contains(&TokenKind::Comma));
+        assert!(expected.contains(&TokenKind::RightBracket));
+    }
+
+    #[test]
+    fn block() {
+        let tokens = tokenize(b"let y = { let x = f(1); x += 2; x * { 3 } } + {};").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::BinaryAddition(addition) = declaration.value else { panic!() };
+        let Expression::Block(block) = addition.left_operand else { panic!() };
+        assert!(matches!(block.statements.as_slice(), [Statement::Let(_), Statement::Assign(_)]));
+        let Some(Expression::BinaryMultiplication(multiplication)) = block.value else { panic!() };
+        let Expression::Block(inner) = multiplication.right_operand else { panic!() };
+        assert!(inner.statements.is_empty());
+        assert!(matches!(inner.value, Some(Expression::IntegerLiteral(_))));
+        let Expression::Block(empty) = addition.right_operand else { panic!() };
+        assert!(empty.statements.is_empty() && empty.value.is_none());
+
+        let tokens = tokenize(b"let y = { let x = 1; };").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Block(block) = declaration.value else { panic!() };
+        assert!(matches!(block.statements.as_slice(), [Statement::Let(_)]) && block.value.is_none());
+
+        let tokens = tokenize(b"let y = { let x = 1 };").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected.last(), Some(&TokenKind::Semicolon));
+
+        let tokens = tokenize(b"let y = { f(x); 1 };").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::Block(block) = declaration.value else { panic!() };
+        let [Statement::Expression(call)] = block.statements.as_slice() else { panic!() };
+        assert!(matches!(**call, Expression::Call(_)));
+        assert!(matches!(block.value, Some(Expression::IntegerLiteral(_))));
+
+        let tokens = tokenize(b"let y = { x y };").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert!(expected.contains(&TokenKind::RightBrace));
+        assert!(expected.contains(&TokenKind::Semicolon));
+        assert!(expected.contains(&TokenKind::Assign));
+    }
+
+    #[test]
+    fn assignment() {
+        let tokens = tokenize(b"x = x + 1; a[i].b = { c[0] = 2; c }; y -= 1;").unwrap();
+        let Ok(statements) = parse_program(&tokens) else { panic!() };
+        let [Statement::Assign(x), Statement::Assign(member), Statement::Assign(y)] = statements.as_slice() else { panic!() };
+        assert!(matches!(x.target, Expression::Identifier(_)));
+        assert!(matches!(x.value, Expression::BinaryAddition(_)));
+        let Expression::MemberAccess(target) = &member.target else { panic!() };
+        assert!(matches!(target.object, Expression::Index(_)));
+        let Expression::Block(block) = &member.value else { panic!() };
+        let [Statement::Assign(index)] = block.statements.as_slice() else { panic!() };
+        assert!(matches!(index.target, Expression::Index(_)));
+        assert!(matches!(y.value, Expression::BinarySubtraction(_)));
+
+        let tokens = tokenize(b"f(x) = 1;").unwrap();
+        assert!(matches!(parse(&tokens), Err(Error::InvalidAssignmentTarget { .. })));
+        let tokens = tokenize(b"a?.b = 1;").unwrap();
+        assert!(matches!(parse(&tokens), Err(Error::InvalidAssignmentTarget { .. })));
+        let tokens = tokenize(b"a[0] += 1;").unwrap();
+        assert!(matches!(parse(&tokens), Err(Error::InvalidAssignmentTarget { .. })));
+    }
+
+    #[test]
+    fn program() {
+        let tokens = tokenize(b"let x = 1; x += 2;\nf(x);\nlet y = x;").unwrap();
+        let Ok(statements) = parse_program(&tokens) else { panic!() };
+        assert!(matches!(statements.as_slice(), [
+            Statement::Let(_),
+            Statement::Assign(_),
+            Statement::Expression(_),
+            Statement::Let(_),
+        ]));
+
+        let tokens = tokenize(b"").unwrap();
+        assert!(matches!(parse_program(&tokens), Ok(statements) if statements.is_empty()));
+
+        let tokens = tokenize(b"let x = 1 let y = 2;").unwrap();
+        let Err(Error::UnexpectedToken { found, expected, offset }) = parse_program(&tokens) else { panic!() };
+        assert_eq!((found, offset), (TokenKind::Let, 4));
+        assert_eq!(expected.last(), Some(&TokenKind::Semicolon));
+
+        let tokens = tokenize(b"let x = 1; let y = (x)").unwrap();
+        let error = parse_program(&tokens).unwrap_err();
+        assert!(error.to_string().starts_with("unexpected end of input at offset 11, expected `(`, `[`, `.`"));
+        assert!(error.to_string().ends_with(", `|>` or `;`"));
+
+        let tokens = tokenize(b"let x = 1;;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(expected, [vec![TokenKind::EOF, TokenKind::For, TokenKind::Struct, TokenKind::Enum, TokenKind::Function, TokenKind::Let], expression_tokens()].concat());
+    }
+
+    #[test]
+    fn for_loop() {
+        let tokens = tokenize(b"for x in xs { total += x; } let y = 1; for c in f(\"ab\") { for d in [c] { g(d) } }").unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::For(first), Statement::Let(_), Statement::For(second)] = statements.as_slice() else { panic!() };
+        assert_eq!(first.binding, b"x");
+        assert!(matches!(first.iterable, Expression::Identifier(_)));
+        assert!(matches!(first.body.statements.as_slice(), [Statement::Assign(_)]));
+        assert_eq!(first.body.value, None);
+        let [Statement::For(inner)] = second.body.statements.as_slice() else { panic!() };
+        assert!(matches!(inner.iterable, Expression::ArrayLiteral(_)));
+        assert!(matches!(inner.body.value, Some(Expression::Call(_))));
+
+        let tokens = tokenize(b"let x = { for y in z {} 1 };").unwrap();
+        assert!(parse(&tokens).is_ok());
+
+        let tokens = tokenize(b"for x in xs { f(x); };").unwrap();
+        let Err(Error::UnexpectedToken { found, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::Semicolon);
+
+        let tokens = tokenize(b"for x xs {}").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(expected, [TokenKind::In]);
+
+        let tokens = tokenize(b"for x in xs;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(expected.last(), Some(&TokenKind::LeftBrace));
+    }
+
+    #[test]
+    fn structs() {
+        let tokens = tokenize(b"struct Point { x, y } let p = geo::Point { x: 1, y: f(Empty {}) }; for q in [p] {}").unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::Struct(point), Statement::Let(p), Statement::For(_)] = statements.as_slice() else { panic!() };
+        assert_eq!(point.name, b"Point");
+        assert_eq!(point.fields, [b"x".to_vec(), b"y".to_vec()]);
+        let Expression::StructLiteral(literal) = &p.value else { panic!() };
+        assert_eq!(literal.name, [b"geo".to_vec(), b"Point".to_vec()]);
+        let [(x, Expression::IntegerLiteral(_)), (y, Expression::Call(call))] = literal.fields.as_slice() else { panic!() };
+        assert_eq!((x.as_slice(), y.as_slice()), (&b"x"[..], &b"y"[..]));
+        let [Expression::StructLiteral(empty)] = call.arguments.as_slice() else { panic!() };
+        assert!(empty.fields.is_empty());
+
+        // The body of a loop is not taken for the fields of a struct literal,
+        // unless the literal is grouped.
+        let tokens = tokenize(b"for p in points { total += p; }").unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::For(for_loop)] = statements.as_slice() else { panic!() };
+        assert!(matches!(for_loop.iterable, Expression::Identifier(_)));
+        let tokens = tokenize(b"for p in (Points { all: true }).all { struct Inner {} }").unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::For(for_loop)] = statements.as_slice() else { panic!() };
+        assert!(matches!(for_loop.iterable, Expression::MemberAccess(_)));
+        assert!(matches!(for_loop.body.statements.as_slice(), [Statement::Struct(_)]));
+
+        let tokens = tokenize(b"let p = Point { x 1 };").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, [TokenKind::Colon]);
+
+        let tokens = tokenize(b"struct Point { x: 1 }").unwrap();
+        let Err(Error::UnexpectedToken { found, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::Colon);
+    }
+
+    #[test]
+    fn annotations() {
+        let tokens = tokenize(b"let x: int = 3; let mut xs: [[geo::Point]] = []; let y = x;").unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::Let(x), Statement::Let(xs), Statement::Let(y)] = statements.as_slice() else { panic!() };
+        assert_eq!(x.annotation, Some(TypeExpr::Named(vec![b"int".to_vec()])));
+        let point = TypeExpr::Named(vec![b"geo".to_vec(), b"Point".to_vec()]);
+        assert_eq!(xs.annotation, Some(TypeExpr::Array(Box::new(TypeExpr::Array(Box::new(point))))));
+        assert_eq!(y.annotation, None);
+
+        let tokens = tokenize(b"function f(a: int, b) -> float { let c: float = a; c } function g() {}").unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::Function(f), Statement::Function(g)] = statements.as_slice() else { panic!() };
+        assert_eq!(f.name, b"f");
+        let [a, b] = f.parameters.as_slice() else { panic!() };
+        assert_eq!((a.name.as_slice(), a.annotation.as_ref()), (&b"a"[..], Some(&TypeExpr::Named(vec![b"int".to_vec()]))));
+        assert_eq!((b.name.as_slice(), b.annotation.as_ref()), (&b"b"[..], None));
+        assert_eq!(f.return_type, Some(TypeExpr::Named(vec![b"float".to_vec()])));
+        assert!(matches!(f.body.statements.as_slice(), [Statement::Let(_)]));
+        assert!(g.parameters.is_empty() && g.return_type.is_none());
+
+        let tokens = tokenize(b"let x: = 3;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, [TokenKind::LeftParenthesis, TokenKind::LeftBracket, TokenKind::Identifier]);
+
+        let tokens = tokenize(b"function f() -> int;").unwrap();
+        let Err(Error::UnexpectedToken { found, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::Semicolon);
+
+        let tokens = tokenize(b"function f(a: [int) {}").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(expected, [TokenKind::DoubleColon, TokenKind::RightBracket]);
+    }
+
+    #[test]
+    fn tuples() {
+        let tokens = tokenize(b"let t = ((1), (2,), (), (a, b + c));").unwrap();
+        let Statement::Let(t) = parse(&tokens).unwrap() else { panic!() };
+        let Expression::TupleLiteral(elements) = &t.value else { panic!() };
+        let [Expression::IntegerLiteral(_), Expression::TupleLiteral(single), Expression::TupleLiteral(empty), Expression::TupleLiteral(pair)] =
+            elements.as_slice() else { panic!() };
+        assert_eq!((single.len(), empty.len()), (1, 0));
+        assert!(matches!(pair.as_slice(), [Expression::Identifier(_), Expression::BinaryAddition(_)]));
+
+        let tokens = tokenize(b"let mut (a, (b,), (c)): (int, (float,), str) = t;").unwrap();
+        let Statement::Let(declaration) = parse(&tokens).unwrap() else { panic!() };
+        let name = |name: &[u8]| Pattern::Identifier(name.to_vec());
+        let named = |name: &[u8]| TypeExpr::Named(vec![name.to_vec()]);
+        assert_eq!(declaration.pattern, Pattern::Tuple(vec![name(b"a"), Pattern::Tuple(vec![name(b"b")]), name(b"c")]));
+        assert_eq!(declaration.annotation, Some(TypeExpr::Tuple(vec![
+            named(b"int"), TypeExpr::Tuple(vec![named(b"float")]), named(b"str"),
+        ])));
+        assert!(declaration.mutable);
+
+        let tokens = tokenize(b"let x = (1 2);").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected.last(), Some(&TokenKind::RightParenthesis));
+        assert!(expected.contains(&TokenKind::Comma));
+
+        let tokens = tokenize(b"let (a, 1) = t;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, [TokenKind::RightParenthesis, TokenKind::LeftParenthesis, TokenKind::Identifier]);
+    }
+
+    #[test]
+    fn maps() {
+        let tokens = tokenize(b"let m = { \"key\": value, other: { 1: 'c' }, empty: {:} };").unwrap();
+        let Statement::Let(m) = parse(&tokens).unwrap() else { panic!() };
+        let Expression::MapLiteral(entries) = &m.value else { panic!() };
+        let [(Expression::StringLiteral(key), Expression::Identifier(_)), (other, Expression::MapLiteral(inner)), (_, Expression::MapLiteral(empty))] =
+            entries.as_slice() else { panic!() };
+        assert_eq!(**key, b"key");
+        assert_eq!(*other, Expression::StringLiteral(Box::new(b"other".to_vec())));
+        assert!(matches!(inner.as_slice(), [(Expression::IntegerLiteral(_), Expression::CharacterLiteral('c'))]));
+        assert!(empty.is_empty());
+
+        // Blocks stay blocks, empty ones included.
+        let tokens = tokenize(b"let b = [{}, { x }, { a::b }, { let y = 1; y }];").unwrap();
+        let Statement::Let(b) = parse(&tokens).unwrap() else { panic!() };
+        let Expression::ArrayLiteral(elements) = &b.value else { panic!() };
+        assert!(elements.iter().all(|element| matches!(element, Expression::Block(_))));
+
+        let tokens = tokenize(b"let m = { a: 1, f(x): 2 };").unwrap();
+        let Err(Error::UnexpectedToken { found, expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::LeftParenthesis);
+        assert_eq!(expected, [TokenKind::Colon]);
+
+        let tokens = tokenize(b"let m = { a: 1, [b]: 2 };").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, [&[TokenKind::RightBrace][..], MAP_KEY_TOKENS].concat());
+    }
+
+    #[test]
+    fn conditional() {
+        let tokens = tokenize(b"let x = if a < b then f(a) else if c then d else e |> g;").unwrap();
+        let Statement::Let(x) = parse(&tokens).unwrap() else { panic!() };
+        let Expression::Conditional(outer) = &x.value else { panic!() };
+        assert!(matches!(outer.condition, Expression::LessThan(_)));
+        assert!(matches!(outer.consequent, Expression::Call(_)));
+        let Expression::Conditional(inner) = &outer.alternative else { panic!() };
+        assert!(matches!(inner.alternative, Expression::Call(_)));
+
+        // Branches can be conditionals themselves, and an operand has to be grouped.
+        let tokens = tokenize(b"let y = 1 + (if if a then b else c then { d } else [e]) * 2;").unwrap();
+        let Statement::Let(y) = parse(&tokens).unwrap() else { panic!() };
+        let Expression::BinaryAddition(sum) = &y.value else { panic!() };
+        let Expression::BinaryMultiplication(product) = &sum.right_operand else { panic!() };
+        let Expression::Conditional(conditional) = &product.left_operand else { panic!() };
+        assert!(matches!(conditional.condition, Expression::Conditional(_)));
+        assert!(matches!(conditional.consequent, Expression::Block(_)));
+
+        let tokens = tokenize(b"let z = 1 + if a then b else c;").unwrap();
+        let Err(Error::UnexpectedToken { found, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::If);
+
+        let tokens = tokenize(b"let z = if a then b;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected.last(), Some(&TokenKind::Else));
+
+        let tokens = tokenize(b"let z = if a b else c;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected.last(), Some(&TokenKind::Then));
+    }
+
+    #[test]
+    fn enums() {
+        let tokens = tokenize(b"enum Shape { Circle(radius), Rectangle(width, height), Empty() } let s = Shape::Circle(1);").unwrap();
+        let statements = parse_program(&tokens).unwrap();
+        let [Statement::Enum(shape), Statement::Let(s)] = statements.as_slice() else { panic!() };
+        assert_eq!(shape.name, b"Shape");
+        let [circle, rectangle, empty] = shape.variants.as_slice() else { panic!() };
+        assert_eq!((circle.name.as_slice(), circle.fields.as_slice()), (&b"Circle"[..], &[b"radius".to_vec()][..]));
+        assert_eq!(rectangle.fields, [b"width".to_vec(), b"height".to_vec()]);
+        assert_eq!((empty.name.as_slice(), empty.fields.len()), (&b"Empty"[..], 0));
+        let Expression::Call(call) = &s.value else { panic!() };
+        assert!(matches!(call.callee, Expression::Path(_)));
+
+        let tokens = tokenize(b"let x = { enum Unit { Unit } Unit::Unit };").unwrap();
+        let Statement::Let(x) = parse(&tokens).unwrap() else { panic!() };
+        let Expression::Block(block) = &x.value else { panic!() };
+        assert!(matches!(block.statements.as_slice(), [Statement::Enum(_)]));
+        assert!(matches!(block.value, Some(Expression::Path(_))));
+
+        let tokens = tokenize(b"enum Shape { Circle(1) }").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(expected, [TokenKind::RightParenthesis, TokenKind::Identifier]);
+
+        let tokens = tokenize(b"enum Shape { Circle(radius }").unwrap();
+        let Err(Error::UnexpectedToken { found, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!(found, TokenKind::RightBrace);
+    }
+
+    #[test]
+    fn errors() {
+        let tokens = tokenize(b"let x = 1 +").unwrap();
+        let Err(Error::UnexpectedEof { expected, offset }) = parse_program(&tokens) else { panic!() };
+        assert_eq!((expected, offset), (operand_tokens(), 5));
+
+        for (script, delimiter, offset) in [
+            (&b"let x = (1 + 2"[..], TokenKind::LeftParenthesis, 3),
+            (b"let x = f(1, ", TokenKind::LeftParenthesis, 4),
+            (b"let x = a[[1]", TokenKind::LeftBracket, 4),
+            (b"let x = { let y = 1", TokenKind::LeftBrace, 3),
+            (b"let x = { y", TokenKind::LeftBrace, 3),
+        ] {
+            let tokens = tokenize(script).unwrap();
+            let Err(Error::UnclosedDelimiter { delimiter: found, offset: at }) = parse_program(&tokens) else { panic!() };
+            assert_eq!((found, at), (delimiter, offset));
+        }
+
+        let tokens = tokenize(b"let x = (1 + 2;").unwrap();
+        assert!(matches!(parse_program(&tokens), Err(Error::UnexpectedToken { found: TokenKind::Semicolon, .. })));
+
+        let tokens = tokenize(b"let x = 1; f(x) = 2;").unwrap();
+        assert!(matches!(parse_program(&tokens), Err(Error::InvalidAssignmentTarget { offset: 5 })));
+
+        let tokens = tokenize(b"let x = 1; let s = \"${x y}\";").unwrap();
+        let Err(Error::UnexpectedToken { found, offset, .. }) = parse_program(&tokens) else { panic!() };
+        assert_eq!((found, offset), (TokenKind::Identifier, 8));
+    }
+
+    #[test]
+    fn precedence() {
+        assert!(BINARY_OPERATORS.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        assert_eq!(BINARY_OPERATORS.last().unwrap().1, TIGHTEST_PRECEDENCE);
+
+        let tokens = tokenize(b"let x = a or b ?? c | d ^ e & f << g + h * i;").unwrap();
+        let tree = parse(&tokens).unwrap();
+        let tokens = tokenize(b"let x = a or (((b ?? (c | (d ^ (e & (f << (g + (h * i)))))))));").unwrap();
+        assert_eq!(tree, parse(&tokens).unwrap());
+
+        let tokens = tokenize(b"let x = a - b - c == d and e;").unwrap();
+        let tree = parse(&tokens).unwrap();
+        let tokens = tokenize(b"let x = (((a - b) - c) == d) and e;").unwrap();
+        assert_eq!(tree, parse(&tokens).unwrap());
+    }
+
+    #[test]
+    fn recovery() {
+        let recover = |script: &[u8]| {
+            let tokens = tokenize(script).unwrap();
+            let (statements, errors) = parse_program_with_recovery(&tokens, &[], &DEFAULT_OPTIONS);
+            let offsets: Vec<usize> = errors.iter().map(Error::offset).collect();
+            (statements, errors, offsets)
+        };
+
+        let (statements, errors, offsets) = recover(b"let x = 1 +; let y = (2 3); let z = x;");
+        assert!(matches!(statements.as_slice(), [Statement::Let(declaration)] if declaration.pattern == Pattern::Identifier(b"z".to_vec())));
+        assert_eq!(offsets, [5, 11]);
+        assert!(matches!(errors[1], Error::UnexpectedToken { found: TokenKind::Integer, .. }));
+
+        let (statements, _, offsets) = recover(b"let x = { let y = 1 2; y = ; y }; x = 1;");
+        let [Statement::Let(declaration), Statement::Assign(_)] = statements.as_slice() else { panic!() };
+        let Expression::Block(block) = &declaration.value else { panic!() };
+        assert!(matches!(block.statements.as_slice(), []));
+        assert!(matches!(block.value, Some(Expression::Identifier(_))));
+        assert_eq!(offsets, [8, 12]);
+
+        let (statements, _, offsets) = recover(b"let x = { f(a b) }; } let y = 2;");
+        assert_eq!(statements.len(), 2);
+        assert_eq!(offsets, [7, 11]);
+
+        let (statements, errors, _) = recover(b"let x = 1; let y = { 1 +");
+        assert_eq!(statements.len(), 1);
+        assert!(matches!(errors.as_slice(), [
+            Error::UnclosedDelimiter { delimiter: TokenKind::LeftBrace, offset: 8 },
+            Error::UnexpectedEof { .. },
+        ]));
+
+        let (statements, errors, offsets) = recover(b"let x = {{{{");
+        assert_eq!(statements.len(), 0);
+        assert_eq!(offsets, [3, 4, 5, 6, 7]);
+        assert!(errors[..4].iter().all(|error| matches!(error, Error::UnclosedDelimiter { delimiter: TokenKind::LeftBrace, .. })));
+        assert!(matches!(errors[4], Error::UnexpectedEof { .. }));
+
+        let (statements, errors, _) = recover(b"let x = 1; x = 2;");
+        assert_eq!((statements.len(), errors.len()), (2, 0));
+    }
+
+    #[test]
+    fn offsets() {
+        let tokens = tokenize(b"let x = (1 +\n  2").unwrap();
+        let offsets = [0, 4, 6, 8, 9, 11, 15, 16];
+        let Err(Error::UnclosedDelimiter { offset, .. }) = parse_program_with_offsets(&tokens, &offsets, &DEFAULT_OPTIONS) else { panic!() };
+        assert_eq!(offset, 8);
+
+        let tokens = tokenize(b"let x = 1 +").unwrap();
+        let offsets = [0, 4, 6, 8, 10, 11];
+        let error = parse_program_with_offsets(&tokens, &offsets, &DEFAULT_OPTIONS).unwrap_err();
+        assert_eq!(error.offset(), 11);
+    }
+
+    #[test]
+    fn options() {
+        let mut lexer_options = LexerOptions::new();
+        lexer_options.keyword("count").keyword("unused");
+        let mut options = ParserOptions::new();
+        options.prefix_operator("count", "__count");
+
+        let tokens = tokenize_with_options(b"let n = count ~count rows + 1;", &lexer_options).unwrap();
+        let Ok(Statement::Let(declaration)) = parse_with_options(&tokens, &options) else { panic!() };
+        let Expression::BinaryAddition(addition) = declaration.value else { panic!() };
+        let Expression::Call(outer) = addition.left_operand else { panic!() };
+        assert!(matches!(&outer.callee, Expression::Identifier(name) if name.as_slice() == b"__count"));
+        let [Expression::BitwiseNot(not)] = outer.arguments.as_slice() else { panic!() };
+        let Expression::Call(inner) = &not.operand else { panic!() };
+        assert!(matches!(inner.arguments.as_slice(), [Expression::Identifier(_)]));
+
+        let (_, spans) = parse_program_with_spans(&tokens, &[], &options).unwrap();
+        assert_eq!(spans, [0..10, 3..9, 3..7, 3..4, 4..7, 5..7, 5..6, 6..7, 8..9]);
+
+        let tokens = tokenize_with_options(b"let n = unused 1;", &lexer_options).unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse_with_options(&tokens, &options) else { panic!() };
+        assert!(expected.contains(&TokenKind::Extension));
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert!(!expected.contains(&TokenKind::Extension));
+    }
+
+    #[test]
+    fn path() {
+        let tokens = tokenize(b"let x = a::b::c + d;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::BinaryAddition(addition) = declaration.value else { panic!() };
+        let Expression::Path(segments) = addition.left_operand else { panic!() };
+        assert_eq!(*segments, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert!(matches!(addition.right_operand, Expression::Identifier(_)));
+
+        let tokens = tokenize(b"let x = a::1;").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, vec![TokenKind::Identifier]);
+    }
+
+    #[test]
+    fn member() {
+        let tokens = tokenize(b"let x = a.b?.c.d(1)[0].e;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::MemberAccess(e) = declaration.value else { panic!() };
+        assert_eq!(e.name, b"e");
+        let Expression::Index(index) = e.object else { panic!() };
+        let Expression::Call(call) = index.object else { panic!() };
+        let Expression::MemberAccess(d) = call.callee else { panic!() };
+        let Expression::OptionalMember(c) = d.object else { panic!() };
+        let Expression::MemberAccess(b) = c.object else { panic!() };
+        assert!(matches!(b.object, Expression::Identifier(_)));
+
+        let tokens = tokenize(b"let x = a.(b);").unwrap();
+        let Err(Error::UnexpectedToken { expected, .. }) = parse(&tokens) else { panic!() };
+        assert_eq!(expected, vec![TokenKind::Identifier]);
+    }
+
+    #[test]
+    fn optional() {
+        let tokens = tokenize(b"let x = a?.b?.c ?? d | e ?? f;").unwrap();
+        let Ok(Statement::Let(declaration)) = parse(&tokens) else { panic!() };
+        let Expression::NullCoalescing(outer) = declaration.value else { panic!() };
+        assert!(matches!(outer.right_operand, Expression::Identifier(_)));
+        let Expression::NullCoalescing(inner) = outer.left_operand else { panic!() };
+        assert!(matches!(inner.right_operand, Expression::BitwiseOr(_)));
+        let Expression::OptionalMember(c) = inner.left_operand else { panic!() };
+        assert_eq!(c.name, b"c");
+        let Expression::OptionalMember(b) = c.object else { panic!() };
+        assert_eq!(b.name, b"b");
+        assert!(matches!(b.object, Expression::Identifier(_)));
+    }
+
+    #[test]
+    fn spans() {
+        /// Counts the statements and expressions visited.
+        struct Nodes(usize);
+
+        impl Visit for Nodes {
+            fn visit_statement(self: &mut Self, statement: &Statement) {
+                self.0 += 1;
+                walk_statement(self, statement);
+            }
+
+            fn visit_expression(self: &mut Self, expression: &Expression) {
+                self.0 += 1;
+                walk_expression(self, expression);
+            }
+        }
+
+        fn sources(script: &str) -> Vec<&str> {
+            let mut tokens = vec![];
+            let mut token_spans = vec![];
+            let mut offset = 0;
+            for token in tokenize_with_trivia(script.as_bytes()).unwrap() {
+                offset += token.leading.len();
+                token_spans.push(offset..offset + token.text.len());
+                offset += token.text.len() + token.trailing.len();
+                tokens.push(token.token);
+            }
+            let (statements, spans) = parse_program_with_spans(&tokens, &token_spans, &DEFAULT_OPTIONS).unwrap();
+            let mut nodes = Nodes(0);
+            for statement in statements.iter() {
+                nodes.visit_statement(statement);
+            }
+            assert_eq!(nodes.0, spans.len());
+            spans.into_iter().map(|span| &script[span]).collect()
+        }
+
+        assert_eq!(sources("let x = (a + b) * c[0] ;"), [
+            "let x = (a + b) * c[0] ;", "(a + b) * c[0]", "a + b", "a", "b", "c[0]", "c", "0",
+        ]);
+        assert_eq!(sources("x += -f(1);\nfor y in ys { y.z = {k: \"${y}\"}; }"), [
+            "x += -f(1);", "x", "x += -f(1)", "x", "-f(1)", "f(1)", "f", "1",
+            "for y in ys { y.z = {k: \"${y}\"}; }", "ys",
+            "y.z = {k: \"${y}\"};", "y.z", "y", "{k: \"${y}\"}", "k", "\"${y}\"", "\"${y}\"",
+        ]);
+        assert_eq!(sources("a + x |> o.f(1)(2) == y |> g;"), [
+            "a + x |> o.f(1)(2) == y |> g;", "a + x |> o.f(1)(2) == y |> g", "g",
+            "a + x |> o.f(1)(2) == y", "a + x |> o.f(1)(2)", "o.f(1)", "o.f", "o", "1",
+            "a + x", "a", "x", "2", "y",
+        ]);
+    }
+}
@@ -0,0 +1,363 @@
+//! Indented, human-readable dump of the AST, for debugging.
+//!
+//! Every node takes a line with its name and, for leaves, its value as
+//! written in Bark, followed by its children indented by two spaces:
+//!
+//! ```text
+//! Let total
+//!   BinaryMultiplication
+//!     Identifier price
+//!     IntegerLiteral 0x10
+//! ```
+//!
+//! With the spans returned by `parse_script_with_spans`, [`program_with_spans`]
+//! also shows the bytes every statement and expression was parsed from, as in
+//! `Let total @ 0..22`.
+//!
+//! The format is meant for people and may change at any time; the `json`
+//! module has a stable one.
+
+use std::ops::Range;
+use std::slice;
+use crate::lexer::{render, Token};
+use crate::parser::{BinaryOperation, Block, Expression, Statement};
+
+/// The spelling of a literal token, as in the source.
+fn spelling(token: Token) -> String {
+    String::from_utf8_lossy(&render(&[token])).into_owned()
+}
+
+struct Printer<'a> {
+    output: String,
+    depth: usize,
+    /// The spans of the nodes left to print, if they are shown.
+    spans: Option<slice::Iter<'a, Range<usize>>>,
+    /// The span of the node whose line comes next.
+    span: Option<Range<usize>>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(spans: Option<&'a [Range<usize>]>) -> Self {
+        Printer { output: String::new(), depth: 0, spans: spans.map(<[_]>::iter), span: None }
+    }
+
+    /// Takes the span of the node about to be printed.
+    fn enter(self: &mut Self) {
+        if let Some(spans) = &mut self.spans {
+            self.span = Some(spans.next().expect("every node has a span").clone());
+        }
+    }
+
+    fn line(self: &mut Self, text: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(text);
+        if let Some(span) = self.span.take() {
+            self.output.push_str(&format!(" @ {}..{}", span.start, span.end));
+        }
+        self.output.push('\n');
+    }
+
+    /// Writes the line of a node, then its `children` below it.
+    fn node<'b>(self: &mut Self, text: &str, children: impl IntoIterator<Item = &'b Expression>) {
+        self.line(text);
+        self.depth += 1;
+        for child in children {
+            self.expression(child);
+        }
+        self.depth -= 1;
+    }
+
+    fn statement(self: &mut Self, statement: &Statement) {
+        self.enter();
+        match statement {
+            Statement::Let(declaration) => {
+                let mutable = if declaration.mutable { "mut " } else { "" };
+                let mut text = format!("Let {}{}", mutable, declaration.pattern);
+                if let Some(annotation) = &declaration.annotation {
+                    text.push_str(&format!(": {}", annotation));
+                }
+                self.node(&text, [&declaration.value]);
+            },
+            Statement::Assign(assignment) => {
+                self.node("Assign", [&assignment.target, &assignment.value]);
+            },
+            Statement::Expression(expression) => {
+                self.node("Expression", [&**expression]);
+            },
+            Statement::For(for_loop) => {
+                self.line(&format!("For {}", String::from_utf8_lossy(&for_loop.binding)));
+                self.depth += 1;
+                self.expression(&for_loop.iterable);
+                self.block(&for_loop.body);
+                self.depth -= 1;
+            },
+            Statement::Struct(declaration) => {
+                self.line(&format!("Struct {}", String::from_utf8_lossy(&declaration.name)));
+                self.depth += 1;
+                for field in declaration.fields.iter() {
+                    self.line(&format!("Field {}", String::from_utf8_lossy(field)));
+                }
+                self.depth -= 1;
+            },
+            Statement::Function(function) => {
+                let parameters: Vec<_> = function.parameters.iter()
+                    .map(|parameter| match &parameter.annotation {
+                        Some(annotation) => format!("{}: {}", String::from_utf8_lossy(&parameter.name), annotation),
+                        None => String::from_utf8_lossy(&parameter.name).into_owned(),
+                    })
+                    .collect();
+                let mut text = format!("Function {}({})", String::from_utf8_lossy(&function.name), parameters.join(", "));
+                if let Some(return_type) = &function.return_type {
+                    text.push_str(&format!(" -> {}", return_type));
+                }
+                self.line(&text);
+                self.depth += 1;
+                self.block(&function.body);
+                self.depth -= 1;
+            },
+            Statement::Enum(declaration) => {
+                self.line(&format!("Enum {}", String::from_utf8_lossy(&declaration.name)));
+                self.depth += 1;
+                for variant in declaration.variants.iter() {
+                    let mut text = format!("Variant {}", String::from_utf8_lossy(&variant.name));
+                    if !variant.fields.is_empty() {
+                        let fields: Vec<_> = variant.fields.iter().map(|field| String::from_utf8_lossy(field)).collect();
+                        text.push_str(&format!("({})", fields.join(", ")));
+                    }
+                    self.line(&text);
+                }
+                self.depth -= 1;
+            },
+        }
+    }
+
+    fn block(self: &mut Self, block: &Block) {
+        self.line("Block");
+        self.depth += 1;
+        for statement in block.statements.iter() {
+            self.statement(statement);
+        }
+        if let Some(value) = &block.value {
+            self.expression(value);
+        }
+        self.depth -= 1;
+    }
+
+    fn binary(self: &mut Self, name: &str, operation: &BinaryOperation) {
+        self.node(name, [&operation.left_operand, &operation.right_operand]);
+    }
+
+    fn expression(self: &mut Self, expression: &Expression) {
+        self.enter();
+        match expression {
+            Expression::Identifier(name) => {
+                self.line(&format!("Identifier {}", String::from_utf8_lossy(name)));
+            },
+            Expression::Path(segments) => {
+                let segments: Vec<_> = segments.iter().map(|segment| String::from_utf8_lossy(segment)).collect();
+                self.line(&format!("Path {}", segments.join("::")));
+            },
+            Expression::IntegerLiteral(integer) => {
+                self.line(&format!("IntegerLiteral {}", spelling(Token::Integer(integer.clone()))));
+            },
+            Expression::FloatLiteral(float) => {
+                self.line(&format!("FloatLiteral {}", spelling(Token::Float(float.clone()))));
+            },
+            Expression::BooleanLiteral(boolean) => {
+                self.line(&format!("BooleanLiteral {}", boolean));
+            },
+            Expression::CharacterLiteral(character) => {
+                self.line(&format!("CharacterLiteral {}", spelling(Token::Character(*character))));
+            },
+            Expression::StringLiteral(string) => {
+                self.line(&format!("StringLiteral {}", spelling(Token::String(string.clone()))));
+            },
+            Expression::InterpolatedString(parts) => {
+                self.node("InterpolatedString", parts.iter());
+            },
+            Expression::ArrayLiteral(elements) => {
+                self.node("ArrayLiteral", elements.iter());
+            },
+            Expression::TupleLiteral(elements) => {
+                self.node("TupleLiteral", elements.iter());
+            },
+            Expression::MapLiteral(entries) => {
+                self.line("MapLiteral");
+                self.depth += 1;
+                for (key, value) in entries.iter() {
+                    self.node("Entry", [key, value]);
+                }
+                self.depth -= 1;
+            },
+            Expression::Call(call) => {
+                self.node("Call", [&call.callee].into_iter().chain(call.arguments.iter()));
+            },
+            Expression::Index(index) => {
+                self.node("Index", [&index.object, &index.index]);
+            },
+            Expression::MemberAccess(member) => {
+                let text = format!("MemberAccess .{}", String::from_utf8_lossy(&member.name));
+                self.node(&text, [&member.object]);
+            },
+            Expression::OptionalMember(member) => {
+                let text = format!("OptionalMember ?.{}", String::from_utf8_lossy(&member.name));
+                self.node(&text, [&member.object]);
+            },
+            Expression::Block(block) => self.block(block),
+            Expression::Conditional(conditional) => {
+                self.node("Conditional", [&conditional.condition, &conditional.consequent, &conditional.alternative]);
+            },
+            Expression::StructLiteral(literal) => {
+                let segments: Vec<_> = literal.name.iter().map(|segment| String::from_utf8_lossy(segment)).collect();
+                self.line(&format!("StructLiteral {}", segments.join("::")));
+                self.depth += 1;
+                for (name, value) in literal.fields.iter() {
+                    self.node(&format!("Field {}", String::from_utf8_lossy(name)), [value]);
+                }
+                self.depth -= 1;
+            },
+            Expression::UnaryAddition(unary) => self.node("UnaryAddition", [&unary.operand]),
+            Expression::UnarySubtraction(unary) => self.node("UnarySubtraction", [&unary.operand]),
+            Expression::LogicalNot(unary) => self.node("LogicalNot", [&unary.operand]),
+            Expression::BitwiseNot(unary) => self.node("BitwiseNot", [&unary.operand]),
+            Expression::BinaryAddition(binary) => self.binary("BinaryAddition", binary),
+            Expression::BinarySubtraction(binary) => self.binary("BinarySubtraction", binary),
+            Expression::BinaryMultiplication(binary) => self.binary("BinaryMultiplication", binary),
+            Expression::BinaryDivision(binary) => self.binary("BinaryDivision", binary),
+            Expression::BinaryFloorDivision(binary) => self.binary("BinaryFloorDivision", binary),
+            Expression::BinaryRemainder(binary) => self.binary("BinaryRemainder", binary),
+            Expression::LogicalAnd(binary) => self.binary("LogicalAnd", binary),
+            Expression::LogicalOr(binary) => self.binary("LogicalOr", binary),
+            Expression::LogicalXor(binary) => self.binary("LogicalXor", binary),
+            Expression::BitwiseAnd(binary) => self.binary("BitwiseAnd", binary),
+            Expression::BitwiseOr(binary) => self.binary("BitwiseOr", binary),
+            Expression::BitwiseXor(binary) => self.binary("BitwiseXor", binary),
+            Expression::BitwiseLeftShift(binary) => self.binary("BitwiseLeftShift", binary),
+            Expression::BitwiseRightShift(binary) => self.binary("BitwiseRightShift", binary),
+            Expression::NullCoalescing(binary) => self.binary("NullCoalescing", binary),
+            Expression::Equals(binary) => self.binary("Equals", binary),
+            Expression::NotEquals(binary) => self.binary("NotEquals", binary),
+            Expression::LessThan(binary) => self.binary("LessThan", binary),
+            Expression::LessThanOrEquals(binary) => self.binary("LessThanOrEquals", binary),
+            Expression::GreaterThan(binary) => self.binary("GreaterThan", binary),
+            Expression::GreaterThanOrEquals(binary) => self.binary("GreaterThanOrEquals", binary),
+        }
+    }
+}
+
+impl Expression {
+    /// Dumps the tree under this node as described in the `pretty` module.
+    pub fn pretty(self: &Self) -> String {
+        let mut printer = Printer::new(None);
+        printer.expression(self);
+        printer.output
+    }
+}
+
+impl Statement {
+    /// Dumps the tree under this statement as described in the `pretty`
+    /// module.
+    pub fn pretty(self: &Self) -> String {
+        let mut printer = Printer::new(None);
+        printer.statement(self);
+        printer.output
+    }
+}
+
+/// Dumps the statements of a script, each node followed by its span.
+pub fn program_with_spans(statements: &[Statement], spans: &[Range<usize>]) -> String {
+    let mut printer = Printer::new(Some(spans));
+    for statement in statements.iter() {
+        printer.statement(statement);
+    }
+    assert!(printer.spans.unwrap().next().is_none(), "every span belongs to a node");
+    printer.output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    #[test]
+    fn pretty() {
+        let tokens = tokenize(b"let mut x = f(-1.5e3, \"a\\n${b}\")?.c[0xff] + { let y = 'z'; y.w = true; a::b };").unwrap();
+        let statement = parse(&tokens).unwrap();
+        assert_eq!(statement.pretty(), concat!(
+            "Let mut x\n",
+            "  BinaryAddition\n",
+            "    Index\n",
+            "      OptionalMember ?.c\n",
+            "        Call\n",
+            "          Identifier f\n",
+            "          UnarySubtraction\n",
+            "            FloatLiteral 1.5e3\n",
+            "          InterpolatedString\n",
+            "            StringLiteral \"a\\n\"\n",
+            "            Identifier b\n",
+            "      IntegerLiteral 0xff\n",
+            "    Block\n",
+            "      Let y\n",
+            "        CharacterLiteral 'z'\n",
+            "      Assign\n",
+            "        MemberAccess .w\n",
+            "          Identifier y\n",
+            "        BooleanLiteral true\n",
+            "      Path a::b\n",
+        ));
+
+        let statement = parse(&tokenize(b"f(x);").unwrap()).unwrap();
+        assert_eq!(statement.pretty(), "Expression\n  Call\n    Identifier f\n    Identifier x\n");
+    }
+
+    #[test]
+    fn declarations() {
+        let pretty = |source: &[u8]| parse(&tokenize(source).unwrap()).unwrap().pretty();
+        assert_eq!(pretty(b"struct Point { x, y }"), "Struct Point\n  Field x\n  Field y\n");
+        assert_eq!(pretty(b"struct Unit {}"), "Struct Unit\n");
+        assert_eq!(pretty(b"enum Shape { Circle(r), Rectangle(w, h), Empty }"), concat!(
+            "Enum Shape\n",
+            "  Variant Circle(r)\n",
+            "  Variant Rectangle(w, h)\n",
+            "  Variant Empty\n",
+        ));
+        assert_eq!(pretty(b"function area(w: int, h) -> float { w * h }"), concat!(
+            "Function area(w: int, h) -> float\n",
+            "  Block\n",
+            "    BinaryMultiplication\n",
+            "      Identifier w\n",
+            "      Identifier h\n",
+        ));
+        assert_eq!(pretty(b"for p in points { total += p; }"), concat!(
+            "For p\n",
+            "  Identifier points\n",
+            "  Block\n",
+            "    Assign\n",
+            "      Identifier total\n",
+            "      BinaryAddition\n",
+            "        Identifier total\n",
+            "        Identifier p\n",
+        ));
+        assert_eq!(pretty(b"let (a, b): (int, [str]) = pair;"), "Let (a, b): (int, [str])\n  Identifier pair\n");
+    }
+
+    #[test]
+    fn spans() {
+        let (statements, spans) = crate::parse_script_with_spans(b"let m = {k: [1]};\nfor p in ps { p; }").unwrap();
+        assert_eq!(super::program_with_spans(&statements, &spans), concat!(
+            "Let m @ 0..17\n",
+            "  MapLiteral @ 8..16\n",
+            "    Entry\n",
+            "      StringLiteral \"k\" @ 9..10\n",
+            "      ArrayLiteral @ 12..15\n",
+            "        IntegerLiteral 1 @ 13..14\n",
+            "For p @ 18..36\n",
+            "  Identifier ps @ 27..29\n",
+            "  Block\n",
+            "    Expression @ 32..34\n",
+            "      Identifier p @ 32..33\n",
+        ));
+    }
+}
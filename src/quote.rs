@@ -0,0 +1,44 @@
+//! Runtime half of the `quote!` macro.
+//!
+//! `quote!` checks its Bark source at compile time and expands to a call to
+//! [`expand`], which parses the same source and replaces the placeholder
+//! identifiers standing for `#{...}` splices with the spliced nodes.
+
+use crate::lexer::tokenize;
+use crate::parser::{parse, Expression, Statement};
+use crate::visit::{walk_expression_mut, VisitMut};
+
+pub use bark_macros::quote;
+
+pub const SPLICE_PREFIX: &str = "__bark_splice_";
+
+fn splice_index(name: &[u8]) -> Option<usize> {
+    std::str::from_utf8(name.strip_prefix(SPLICE_PREFIX.as_bytes())?).ok()?.parse().ok()
+}
+
+/// Replaces the placeholders with their splices.
+struct Substitution {
+    splices: Vec<Option<Expression>>,
+}
+
+impl VisitMut for Substitution {
+    fn visit_expression_mut(self: &mut Self, expression: &mut Expression) {
+        if let Expression::Identifier(name) = expression {
+            if let Some(splice) = splice_index(name).and_then(|index| self.splices.get_mut(index)) {
+                *expression = splice.take().expect("each splice is used once");
+                return;
+            }
+        }
+        walk_expression_mut(self, expression);
+    }
+}
+
+/// Parses `source` generated by `quote!` and substitutes `splices` in order.
+pub fn expand(source: &str, splices: Vec<Expression>) -> Statement {
+    let tokens = tokenize(source.as_bytes()).expect("quote! checks the source at compile time");
+    let mut ast = parse(&tokens).expect("quote! checks the source at compile time");
+    let mut substitution = Substitution { splices: splices.into_iter().map(Some).collect() };
+    substitution.visit_statement_mut(&mut ast);
+    assert!(substitution.splices.iter().all(Option::is_none), "every splice stands for an expression");
+    ast
+}
@@ -0,0 +1,455 @@
+//! Traversal of the AST.
+//!
+//! [`Visit`] and [`VisitMut`] have a method per kind of node, which by
+//! default walks the children of the node through the matching `walk_*`
+//! function. An analysis overrides the methods of the nodes it is interested
+//! in, and calls the `walk_*` function from them to keep going down the tree.
+
+use crate::parser::{
+    Assignment, BinaryOperation, Block, Call, Conditional, Declaration, Expression, For, Function, Index,
+    Member, Statement, StructLiteral, UnaryOperation,
+};
+
+pub trait Visit {
+    fn visit_statement(self: &mut Self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_declaration(self: &mut Self, declaration: &Declaration) {
+        walk_declaration(self, declaration);
+    }
+
+    fn visit_assignment(self: &mut Self, assignment: &Assignment) {
+        walk_assignment(self, assignment);
+    }
+
+    fn visit_for(self: &mut Self, for_loop: &For) {
+        walk_for(self, for_loop);
+    }
+
+    fn visit_function(self: &mut Self, function: &Function) {
+        walk_function(self, function);
+    }
+
+    fn visit_expression(self: &mut Self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_identifier(self: &mut Self, _name: &[u8]) {}
+
+    fn visit_unary_operation(self: &mut Self, operation: &UnaryOperation) {
+        walk_unary_operation(self, operation);
+    }
+
+    fn visit_binary_operation(self: &mut Self, operation: &BinaryOperation) {
+        walk_binary_operation(self, operation);
+    }
+
+    fn visit_call(self: &mut Self, call: &Call) {
+        walk_call(self, call);
+    }
+
+    fn visit_index(self: &mut Self, index: &Index) {
+        walk_index(self, index);
+    }
+
+    /// Visits both `object.name` and `object?.name`.
+    fn visit_member(self: &mut Self, member: &Member) {
+        walk_member(self, member);
+    }
+
+    fn visit_block(self: &mut Self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_struct_literal(self: &mut Self, literal: &StructLiteral) {
+        walk_struct_literal(self, literal);
+    }
+
+    fn visit_conditional(self: &mut Self, conditional: &Conditional) {
+        walk_conditional(self, conditional);
+    }
+}
+
+pub fn walk_statement<V: Visit + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Let(declaration) => visitor.visit_declaration(declaration),
+        Statement::Assign(assignment) => visitor.visit_assignment(assignment),
+        Statement::For(for_loop) => visitor.visit_for(for_loop),
+        Statement::Struct(_) | Statement::Enum(_) => {},
+        Statement::Function(function) => visitor.visit_function(function),
+        Statement::Expression(expression) => visitor.visit_expression(expression),
+    }
+}
+
+pub fn walk_for<V: Visit + ?Sized>(visitor: &mut V, for_loop: &For) {
+    visitor.visit_expression(&for_loop.iterable);
+    visitor.visit_block(&for_loop.body);
+}
+
+pub fn walk_function<V: Visit + ?Sized>(visitor: &mut V, function: &Function) {
+    visitor.visit_block(&function.body);
+}
+
+pub fn walk_declaration<V: Visit + ?Sized>(visitor: &mut V, declaration: &Declaration) {
+    visitor.visit_expression(&declaration.value);
+}
+
+pub fn walk_assignment<V: Visit + ?Sized>(visitor: &mut V, assignment: &Assignment) {
+    visitor.visit_expression(&assignment.target);
+    visitor.visit_expression(&assignment.value);
+}
+
+pub fn walk_expression<V: Visit + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Identifier(name) => visitor.visit_identifier(name),
+        Expression::Path(_)
+        | Expression::IntegerLiteral(_)
+        | Expression::FloatLiteral(_)
+        | Expression::BooleanLiteral(_)
+        | Expression::CharacterLiteral(_)
+        | Expression::StringLiteral(_) => {},
+        Expression::InterpolatedString(parts)
+        | Expression::ArrayLiteral(parts)
+        | Expression::TupleLiteral(parts) => {
+            for part in parts.iter() {
+                visitor.visit_expression(part);
+            }
+        },
+        Expression::MapLiteral(entries) => {
+            for (key, value) in entries.iter() {
+                visitor.visit_expression(key);
+                visitor.visit_expression(value);
+            }
+        },
+        Expression::UnaryAddition(unary)
+        | Expression::UnarySubtraction(unary)
+        | Expression::LogicalNot(unary)
+        | Expression::BitwiseNot(unary) => visitor.visit_unary_operation(unary),
+        Expression::Call(call) => visitor.visit_call(call),
+        Expression::Index(index) => visitor.visit_index(index),
+        Expression::MemberAccess(member)
+        | Expression::OptionalMember(member) => visitor.visit_member(member),
+        Expression::Block(block) => visitor.visit_block(block),
+        Expression::StructLiteral(literal) => visitor.visit_struct_literal(literal),
+        Expression::Conditional(conditional) => visitor.visit_conditional(conditional),
+        Expression::BinaryAddition(binary)
+        | Expression::BinarySubtraction(binary)
+        | Expression::BinaryMultiplication(binary)
+        | Expression::BinaryDivision(binary)
+        | Expression::BinaryFloorDivision(binary)
+        | Expression::BinaryRemainder(binary)
+        | Expression::LogicalAnd(binary)
+        | Expression::LogicalOr(binary)
+        | Expression::LogicalXor(binary)
+        | Expression::BitwiseAnd(binary)
+        | Expression::BitwiseOr(binary)
+        | Expression::BitwiseXor(binary)
+        | Expression::BitwiseLeftShift(binary)
+        | Expression::BitwiseRightShift(binary)
+        | Expression::NullCoalescing(binary)
+        | Expression::Equals(binary)
+        | Expression::NotEquals(binary)
+        | Expression::LessThan(binary)
+        | Expression::LessThanOrEquals(binary)
+        | Expression::GreaterThan(binary)
+        | Expression::GreaterThanOrEquals(binary) => visitor.visit_binary_operation(binary),
+    }
+}
+
+pub fn walk_unary_operation<V: Visit + ?Sized>(visitor: &mut V, operation: &UnaryOperation) {
+    visitor.visit_expression(&operation.operand);
+}
+
+pub fn walk_binary_operation<V: Visit + ?Sized>(visitor: &mut V, operation: &BinaryOperation) {
+    visitor.visit_expression(&operation.left_operand);
+    visitor.visit_expression(&operation.right_operand);
+}
+
+pub fn walk_call<V: Visit + ?Sized>(visitor: &mut V, call: &Call) {
+    visitor.visit_expression(&call.callee);
+    for argument in call.arguments.iter() {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_index<V: Visit + ?Sized>(visitor: &mut V, index: &Index) {
+    visitor.visit_expression(&index.object);
+    visitor.visit_expression(&index.index);
+}
+
+pub fn walk_member<V: Visit + ?Sized>(visitor: &mut V, member: &Member) {
+    visitor.visit_expression(&member.object);
+}
+
+pub fn walk_block<V: Visit + ?Sized>(visitor: &mut V, block: &Block) {
+    for statement in block.statements.iter() {
+        visitor.visit_statement(statement);
+    }
+    if let Some(value) = &block.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_conditional<V: Visit + ?Sized>(visitor: &mut V, conditional: &Conditional) {
+    visitor.visit_expression(&conditional.condition);
+    visitor.visit_expression(&conditional.consequent);
+    visitor.visit_expression(&conditional.alternative);
+}
+
+pub fn walk_struct_literal<V: Visit + ?Sized>(visitor: &mut V, literal: &StructLiteral) {
+    for (_, value) in literal.fields.iter() {
+        visitor.visit_expression(value);
+    }
+}
+
+/// Like [`Visit`], with mutable access to the nodes, so that they can be
+/// rewritten in place.
+pub trait VisitMut {
+    fn visit_statement_mut(self: &mut Self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_declaration_mut(self: &mut Self, declaration: &mut Declaration) {
+        walk_declaration_mut(self, declaration);
+    }
+
+    fn visit_assignment_mut(self: &mut Self, assignment: &mut Assignment) {
+        walk_assignment_mut(self, assignment);
+    }
+
+    fn visit_for_mut(self: &mut Self, for_loop: &mut For) {
+        walk_for_mut(self, for_loop);
+    }
+
+    fn visit_function_mut(self: &mut Self, function: &mut Function) {
+        walk_function_mut(self, function);
+    }
+
+    fn visit_expression_mut(self: &mut Self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_identifier_mut(self: &mut Self, _name: &mut Vec<u8>) {}
+
+    fn visit_unary_operation_mut(self: &mut Self, operation: &mut UnaryOperation) {
+        walk_unary_operation_mut(self, operation);
+    }
+
+    fn visit_binary_operation_mut(self: &mut Self, operation: &mut BinaryOperation) {
+        walk_binary_operation_mut(self, operation);
+    }
+
+    fn visit_call_mut(self: &mut Self, call: &mut Call) {
+        walk_call_mut(self, call);
+    }
+
+    fn visit_index_mut(self: &mut Self, index: &mut Index) {
+        walk_index_mut(self, index);
+    }
+
+    fn visit_member_mut(self: &mut Self, member: &mut Member) {
+        walk_member_mut(self, member);
+    }
+
+    fn visit_block_mut(self: &mut Self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_struct_literal_mut(self: &mut Self, literal: &mut StructLiteral) {
+        walk_struct_literal_mut(self, literal);
+    }
+
+    fn visit_conditional_mut(self: &mut Self, conditional: &mut Conditional) {
+        walk_conditional_mut(self, conditional);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Let(declaration) => visitor.visit_declaration_mut(declaration),
+        Statement::Assign(assignment) => visitor.visit_assignment_mut(assignment),
+        Statement::For(for_loop) => visitor.visit_for_mut(for_loop),
+        Statement::Struct(_) | Statement::Enum(_) => {},
+        Statement::Function(function) => visitor.visit_function_mut(function),
+        Statement::Expression(expression) => visitor.visit_expression_mut(expression),
+    }
+}
+
+pub fn walk_for_mut<V: VisitMut + ?Sized>(visitor: &mut V, for_loop: &mut For) {
+    visitor.visit_expression_mut(&mut for_loop.iterable);
+    visitor.visit_block_mut(&mut for_loop.body);
+}
+
+pub fn walk_function_mut<V: VisitMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    visitor.visit_block_mut(&mut function.body);
+}
+
+pub fn walk_declaration_mut<V: VisitMut + ?Sized>(visitor: &mut V, declaration: &mut Declaration) {
+    visitor.visit_expression_mut(&mut declaration.value);
+}
+
+pub fn walk_assignment_mut<V: VisitMut + ?Sized>(visitor: &mut V, assignment: &mut Assignment) {
+    visitor.visit_expression_mut(&mut assignment.target);
+    visitor.visit_expression_mut(&mut assignment.value);
+}
+
+pub fn walk_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match expression {
+        Expression::Identifier(name) => visitor.visit_identifier_mut(name),
+        Expression::Path(_)
+        | Expression::IntegerLiteral(_)
+        | Expression::FloatLiteral(_)
+        | Expression::BooleanLiteral(_)
+        | Expression::CharacterLiteral(_)
+        | Expression::StringLiteral(_) => {},
+        Expression::InterpolatedString(parts)
+        | Expression::ArrayLiteral(parts)
+        | Expression::TupleLiteral(parts) => {
+            for part in parts.iter_mut() {
+                visitor.visit_expression_mut(part);
+            }
+        },
+        Expression::MapLiteral(entries) => {
+            for (key, value) in entries.iter_mut() {
+                visitor.visit_expression_mut(key);
+                visitor.visit_expression_mut(value);
+            }
+        },
+        Expression::UnaryAddition(unary)
+        | Expression::UnarySubtraction(unary)
+        | Expression::LogicalNot(unary)
+        | Expression::BitwiseNot(unary) => visitor.visit_unary_operation_mut(unary),
+        Expression::Call(call) => visitor.visit_call_mut(call),
+        Expression::Index(index) => visitor.visit_index_mut(index),
+        Expression::MemberAccess(member)
+        | Expression::OptionalMember(member) => visitor.visit_member_mut(member),
+        Expression::Block(block) => visitor.visit_block_mut(block),
+        Expression::StructLiteral(literal) => visitor.visit_struct_literal_mut(literal),
+        Expression::Conditional(conditional) => visitor.visit_conditional_mut(conditional),
+        Expression::BinaryAddition(binary)
+        | Expression::BinarySubtraction(binary)
+        | Expression::BinaryMultiplication(binary)
+        | Expression::BinaryDivision(binary)
+        | Expression::BinaryFloorDivision(binary)
+        | Expression::BinaryRemainder(binary)
+        | Expression::LogicalAnd(binary)
+        | Expression::LogicalOr(binary)
+        | Expression::LogicalXor(binary)
+        | Expression::BitwiseAnd(binary)
+        | Expression::BitwiseOr(binary)
+        | Expression::BitwiseXor(binary)
+        | Expression::BitwiseLeftShift(binary)
+        | Expression::BitwiseRightShift(binary)
+        | Expression::NullCoalescing(binary)
+        | Expression::Equals(binary)
+        | Expression::NotEquals(binary)
+        | Expression::LessThan(binary)
+        | Expression::LessThanOrEquals(binary)
+        | Expression::GreaterThan(binary)
+        | Expression::GreaterThanOrEquals(binary) => visitor.visit_binary_operation_mut(binary),
+    }
+}
+
+pub fn walk_unary_operation_mut<V: VisitMut + ?Sized>(visitor: &mut V, operation: &mut UnaryOperation) {
+    visitor.visit_expression_mut(&mut operation.operand);
+}
+
+pub fn walk_binary_operation_mut<V: VisitMut + ?Sized>(visitor: &mut V, operation: &mut BinaryOperation) {
+    visitor.visit_expression_mut(&mut operation.left_operand);
+    visitor.visit_expression_mut(&mut operation.right_operand);
+}
+
+pub fn walk_call_mut<V: VisitMut + ?Sized>(visitor: &mut V, call: &mut Call) {
+    visitor.visit_expression_mut(&mut call.callee);
+    for argument in call.arguments.iter_mut() {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+pub fn walk_index_mut<V: VisitMut + ?Sized>(visitor: &mut V, index: &mut Index) {
+    visitor.visit_expression_mut(&mut index.object);
+    visitor.visit_expression_mut(&mut index.index);
+}
+
+pub fn walk_member_mut<V: VisitMut + ?Sized>(visitor: &mut V, member: &mut Member) {
+    visitor.visit_expression_mut(&mut member.object);
+}
+
+pub fn walk_block_mut<V: VisitMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for statement in block.statements.iter_mut() {
+        visitor.visit_statement_mut(statement);
+    }
+    if let Some(value) = &mut block.value {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_conditional_mut<V: VisitMut + ?Sized>(visitor: &mut V, conditional: &mut Conditional) {
+    visitor.visit_expression_mut(&mut conditional.condition);
+    visitor.visit_expression_mut(&mut conditional.consequent);
+    visitor.visit_expression_mut(&mut conditional.alternative);
+}
+
+pub fn walk_struct_literal_mut<V: VisitMut + ?Sized>(visitor: &mut V, literal: &mut StructLiteral) {
+    for (_, value) in literal.fields.iter_mut() {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse_program;
+
+    fn program(script: &[u8]) -> Vec<Statement> {
+        parse_program(&tokenize(script).unwrap()).unwrap()
+    }
+
+    /// Collects every identifier, in source order.
+    struct Identifiers(Vec<Vec<u8>>);
+
+    impl Visit for Identifiers {
+        fn visit_identifier(self: &mut Self, name: &[u8]) {
+            self.0.push(name.to_vec());
+        }
+    }
+
+    #[test]
+    fn visit() {
+        let statements = program(b"let a = f(b, -c)[d].e; g = { let h = i; j ?? \"${k}\" };");
+        let mut identifiers = Identifiers(vec![]);
+        for statement in statements.iter() {
+            identifiers.visit_statement(statement);
+        }
+        let expected: Vec<&[u8]> = vec![b"f", b"b", b"c", b"d", b"g", b"i", b"j", b"k"];
+        assert_eq!(identifiers.0, expected);
+    }
+
+    /// Folds additions of integer literals that fit in a `u64`.
+    struct Folder;
+
+    impl VisitMut for Folder {
+        fn visit_expression_mut(self: &mut Self, expression: &mut Expression) {
+            walk_expression_mut(self, expression);
+            let Expression::BinaryAddition(addition) = expression else { return };
+            let (Expression::IntegerLiteral(left), Expression::IntegerLiteral(right)) =
+                (&addition.left_operand, &addition.right_operand) else { return };
+            let (Some(left), Some(right)) = (left.to_u64(), right.to_u64()) else { return };
+            let sum = program(format!("let x = {};", left + right).as_bytes()).pop();
+            if let Some(Statement::Let(declaration)) = sum {
+                *expression = declaration.value;
+            }
+        }
+    }
+
+    #[test]
+    fn visit_mut() {
+        let mut statements = program(b"let x = 1 + 2 + y + (3 + 4);");
+        for statement in statements.iter_mut() {
+            Folder.visit_statement_mut(statement);
+        }
+        assert_eq!(statements, program(b"let x = 3 + y + 7;"));
+    }
+}
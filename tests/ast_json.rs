@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+
+/// Compares the JSON dump of every `tests/ast_json/*.bark` script against
+/// the `.json` file next to it. Set `BARK_BLESS=1` to rewrite the golden
+/// files after an intentional schema change.
+#[test]
+fn golden() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ast_json");
+    let bless = std::env::var_os("BARK_BLESS").is_some();
+    let mut checked = 0;
+
+    for entry in fs::read_dir(&directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension() != Some("bark".as_ref()) {
+            continue;
+        }
+
+        let script = fs::read(&path).unwrap();
+        let (ast, spans) = bark::parse_script_with_spans(&script).unwrap();
+        let actual = bark::json::ast_to_json_with_spans(&ast, &spans);
+
+        let golden = path.with_extension("json");
+        if bless {
+            fs::write(&golden, &actual).unwrap();
+        } else {
+            let expected = fs::read_to_string(&golden).unwrap();
+            assert_eq!(actual, expected, "{} does not match its golden file", path.display());
+            let imported = bark::json::ast_from_json(&expected).unwrap();
+            assert_eq!(imported, ast, "{} does not round-trip", golden.display());
+        }
+        checked += 1;
+    }
+
+    assert!(checked > 0);
+}
+
+#[test]
+fn import() {
+    use bark::builder::ASTBuilder;
+    use bark::json::{ast_from_json, ast_to_json, ImportError};
+
+    let ast = ast_from_json(r#"{"version": 3, "extra": null, "ast": [{"kind": "let", "name": "s", "mutable": false,
+        "value": {"kind": "string_literal", "value": "\u00e9\ud83d\ude00\n"}}]}"#).unwrap();
+    let expected = ASTBuilder::declare("s", false, ASTBuilder::string("\u{e9}\u{1F600}\n".as_bytes())).unwrap();
+    assert_eq!(ast_to_json(&ast), ast_to_json(&[expected]));
+
+    assert_eq!(ast_from_json(r#"{"version": 3, "ast": {"#).unwrap_err(), ImportError::Syntax(23));
+    assert_eq!(ast_from_json(r#"{"version": 1, "ast": {}}"#).unwrap_err(), ImportError::UnsupportedVersion(1));
+    let invalid = |json: &str| match ast_from_json(json) {
+        Err(ImportError::InvalidNode(reason)) => reason,
+        result => panic!("{:?}", result),
+    };
+    let statement = |node: &str| invalid(&format!(r#"{{"version": 3, "ast": [{}]}}"#, node));
+    let value = |node: &str| statement(&format!(
+        r#"{{"kind": "let", "name": "x", "mutable": false, "value": {}}}"#, node));
+    assert_eq!(invalid(r#"{"version": 3, "ast": {"kind": "identifier", "name": "x"}}"#), "`ast` must be an array");
+    assert_eq!(value(r#"{"kind": "identifier", "name": "let"}"#), "`let` is not a valid identifier");
+    assert_eq!(value(r#"{"kind": "bitwise_not", "children": []}"#), "expected 1 children, found 0");
+    assert_eq!(value(r#"{"kind": "integer_literal", "base": "octal", "digits": "8"}"#),
+        "`8` is not a base 8 number");
+    assert_eq!(value(r#"{"kind": "let", "name": "y", "mutable": false, "value": {"kind": "identifier", "name": "z"}}"#),
+        "unknown node kind `let`");
+    assert_eq!(statement(r#"{"kind": "identifier", "name": "x"}"#),
+        "unknown statement kind `identifier`");
+    assert_eq!(statement(r#"{"kind": "assign", "children": [
+        {"kind": "integer_literal", "base": "decimal", "digits": "1"},
+        {"kind": "integer_literal", "base": "decimal", "digits": "2"}]}"#),
+        "only variables, indexing and member access can be assigned to");
+    assert_eq!(statement(r#"{"kind": "for", "binding": "x",
+        "iterable": {"kind": "identifier", "name": "xs"}, "body": {"kind": "identifier", "name": "x"}}"#),
+        "`body` must be a block");
+    assert_eq!(value(r#"{"kind": "struct_literal", "segments": ["Point"], "fields": [{"name": "x"}]}"#),
+        "missing field `value`");
+    assert_eq!(value(r#"{"kind": "struct_literal", "segments": [], "fields": []}"#),
+        "`` is not a valid identifier");
+    assert_eq!(statement(r#"{"kind": "enum", "name": "E", "variants": [{"name": "A", "fields": [1]}]}"#),
+        "`fields` must hold strings");
+    assert_eq!(statement(r#"{"kind": "let", "name": "x", "mutable": false,
+        "value": {"kind": "identifier", "name": "y"}, "type": {"kind": "array_type", "element": {"kind": "map_type"}}}"#),
+        "unknown type kind `map_type`");
+    assert_eq!(statement(r#"{"kind": "let", "pattern": {"kind": "tuple_pattern", "elements": [
+        {"kind": "identifier_pattern", "name": "if"}]}, "mutable": false, "value": {"kind": "identifier", "name": "t"}}"#),
+        "`if` is not a valid identifier");
+    assert_eq!(value(r#"{"kind": "map_literal", "entries": [{"key": {"kind": "identifier", "name": "k"},
+        "value": {"kind": "identifier", "name": "v"}}]}"#), "map keys must be literals");
+    assert_eq!(value(r#"{"kind": "conditional", "condition": {"kind": "identifier", "name": "c"},
+        "consequent": {"kind": "identifier", "name": "a"}}"#), "missing field `alternative`");
+    assert_eq!(value(r#"{"kind": "loop"}"#), "unknown node kind `loop`");
+}
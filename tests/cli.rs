@@ -0,0 +1,33 @@
+use std::fs;
+use std::process::Command;
+
+fn bark(arguments: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_bark")).args(arguments).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (output.status.success(), stdout, stderr)
+}
+
+#[test]
+fn ast_json() {
+    let path = std::env::temp_dir().join(format!("bark_cli_{}.bark", std::process::id()));
+    fs::write(&path, "let x = 1;\nx += 2;\n").unwrap();
+    let (success, stdout, _) = bark(&["ast", "--json", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert!(success);
+    assert!(stdout.contains("\"span\""), "{}", stdout);
+    let ast = bark::json::ast_from_json(&stdout).unwrap();
+    assert_eq!(ast, bark::parse_script(b"let x = 1;\nx += 2;\n").unwrap());
+
+    fs::write(&path, "let x = 1;\nlet y = f(x,\n  2;").unwrap();
+    let (success, _, stderr) = bark(&["ast", "--json", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert!(!success);
+    let prefix = format!("{}:3:4: syntax error: unexpected `;` at offset 27", path.display());
+    assert!(stderr.starts_with(&prefix), "{}", stderr);
+
+    let (success, _, stderr) = bark(&["ast"]);
+    assert!(!success);
+    assert_eq!(stderr, "usage: bark ast --json <file>\n");
+}
@@ -0,0 +1,30 @@
+use bark::builder::ASTBuilder;
+use bark::json::ast_to_json;
+use bark::lexer::tokenize;
+use bark::parser::parse;
+
+#[test]
+fn quote() {
+    let ast = bark::quote!(let x = (1 + 0xff) * y;);
+    let expected = parse(&tokenize(b"let x = (1 + 0xff) * y;").unwrap()).unwrap();
+    assert_eq!(ast_to_json(&[ast]), ast_to_json(&[expected]));
+
+    let value = ASTBuilder::string(b"spliced");
+    let ast = bark::quote!(total += #{value} << #{ASTBuilder::integer(2)};);
+    let expected = parse(&tokenize(b"total += \"spliced\" << 2;").unwrap()).unwrap();
+    assert_eq!(ast_to_json(&[ast]), ast_to_json(&[expected]));
+}
+
+#[test]
+fn placeholders() {
+    // Placeholders without a splice are left as identifiers.
+    let ast = bark::quote::expand("let y = __bark_splice_3;", vec![]);
+    let expected = parse(&tokenize(b"let y = __bark_splice_3;").unwrap()).unwrap();
+    assert_eq!(ast_to_json(&[ast]), ast_to_json(&[expected]));
+}
+
+#[test]
+#[should_panic(expected = "every splice stands for an expression")]
+fn unused_splice() {
+    bark::quote::expand("let __bark_splice_0 = 2;", vec![ASTBuilder::integer(1)]);
+}
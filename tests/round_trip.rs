@@ -0,0 +1,328 @@
+#![allow(clippy::needless_arbitrary_self_type)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use bark::lexer::{render, tokenize, Token};
+use bark::parser::parse_program;
+
+/// Number of generated programs per run; `BARK_FUZZ_CASES` overrides it and
+/// `BARK_FUZZ_SEED` picks another starting seed.
+const CASES: u64 = 500;
+
+fn directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/round_trip")
+}
+
+/// The JSON dump of the program, or `None` if the tokens don't parse.
+fn dump(tokens: &[Token]) -> Option<String> {
+    let statements = parse_program(tokens).ok()?;
+    Some(bark::json::ast_to_json(&statements))
+}
+
+/// Parses `source`, renders its tokens back to source and parses that again.
+/// Sources that don't parse in the first place pass.
+fn check(source: &[u8]) -> Result<(), String> {
+    let Ok(tokens) = tokenize(source) else { return Ok(()) };
+    let Some(expected) = dump(&tokens) else { return Ok(()) };
+    let rendered = render(&tokens);
+    let tokens = tokenize(&rendered)
+        .map_err(|error| format!("rendered source does not lex: {}", error))?;
+    match dump(&tokens) {
+        None => Err("rendered source does not parse".to_string()),
+        Some(actual) if actual != expected => Err("rendered source parses differently".to_string()),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Removes tokens from a failing source for as long as it keeps failing.
+fn minimize(source: &[u8]) -> Vec<u8> {
+    let mut source = source.to_vec();
+    let mut i = 0;
+    loop {
+        let mut tokens = tokenize(&source).unwrap();
+        if i >= tokens.len() {
+            return source;
+        }
+        tokens.remove(i);
+        let candidate = render(&tokens);
+        if check(&candidate).is_err() {
+            source = candidate;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// A xorshift generator, so failures reproduce from their seed alone.
+struct Random(u64);
+
+impl Random {
+    fn next(self: &mut Self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(self: &mut Self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn pick<'a>(self: &mut Self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+const NAMES: &[&str] = &["a", "b", "value", "x1", "_tmp"];
+const KEYS: &[&str] = &["a", "value", "\"text\"", "0xff", "1.5", "true", "'c'"];
+const LITERALS: &[&str] = &["0", "42", "0xff", "0b101", "0o17", "1.5", "2.", ".5", "1e-3", "true", "false", "'c'", "'\\n'"];
+const STRINGS: &[&str] = &["\"\"", "\"text\"", "\"tab\\t\"", "\"\\$5\""];
+const BINARY_OPERATORS: &[&str] = &[
+    "or", "xor", "and", "??", "|", "^", "&", "<<", ">>", "+", "-", "*", "/", "//", "%",
+];
+const COMPARISON_OPERATORS: &[&str] = &["==", "!=", "<", "<=", ">", ">="];
+const UNARY_OPERATORS: &[&str] = &["-", "+", "not ", "~"];
+const TYPES: &[&str] = &["int", "[float]", "io::File", "[[a::b::c]]", "()", "(int,)", "(str, [(a, b)])"];
+const PATTERNS: &[&str] = &["a", "(a, b)", "(x1,)", "()", "((a, b), _tmp)", "(value)"];
+const ASSIGNMENT_OPERATORS: &[&str] = &["=", "+=", "-=", "*=", "/=", "//=", "%="];
+
+struct Generator {
+    random: Random,
+    /// Set while generating the iterable of a loop, where struct literals
+    /// must be grouped.
+    iterable: bool,
+}
+
+impl Generator {
+    /// Wraps `source` in parentheses, always if `required`, otherwise at
+    /// random.
+    fn group(self: &mut Self, source: String, required: bool) -> String {
+        if required || self.random.below(4) == 0 {
+            format!("({})", source)
+        } else {
+            source
+        }
+    }
+
+    /// Generates a comma-separated list, sometimes with a trailing comma.
+    fn list(self: &mut Self, depth: usize, nested: bool) -> String {
+        let count = self.random.below(4);
+        let items: Vec<String> = (0..count).map(|_| self.expression(depth, nested)).collect();
+        let mut list = items.join(", ");
+        if count > 0 && self.random.below(4) == 0 {
+            list.push(',');
+        }
+        list
+    }
+
+    fn expression(self: &mut Self, depth: usize, nested: bool) -> String {
+        self.node(depth, nested).0
+    }
+
+    /// Generates an expression to use as an operand, grouping it if needed.
+    fn operand(self: &mut Self, depth: usize, nested: bool, required: bool) -> String {
+        let (source, top_level) = self.node(depth, nested);
+        self.group(source, required || top_level)
+    }
+
+    /// Generates an expression; `nested` is set inside interpolations, where
+    /// strings and blocks are left out. The flag is set on comparisons,
+    /// pipelines and conditionals, which can't be used ungrouped as operands.
+    fn node(self: &mut Self, depth: usize, nested: bool) -> (String, bool) {
+        let choices = if depth == 0 { 3 } else { 15 };
+        let source = match self.random.below(choices) {
+            0 => self.random.pick(NAMES).to_string(),
+            1 => self.random.pick(LITERALS).to_string(),
+            2 if nested => self.random.pick(NAMES).to_string(),
+            2 => match self.random.below(3) {
+                0 => self.random.pick(STRINGS).to_string(),
+                _ => format!("\"a${{{}}}b\"", self.expression(depth.saturating_sub(1), true)),
+            },
+            3 => {
+                let operand = self.operand(depth - 1, nested, false);
+                format!("{}{}", self.random.pick(UNARY_OPERATORS), operand)
+            },
+            4 | 5 => {
+                let left = self.operand(depth - 1, nested, false);
+                let right = self.operand(depth - 1, nested, false);
+                format!("{} {} {}", left, self.random.pick(BINARY_OPERATORS), right)
+            },
+            6 => {
+                let left = self.operand(depth - 1, nested, false);
+                let right = self.operand(depth - 1, nested, false);
+                let source = format!("{} {} {}", left, self.random.pick(COMPARISON_OPERATORS), right);
+                return (source, true);
+            },
+            7 => {
+                let callee = self.random.pick(NAMES);
+                format!("{}({})", callee, self.list(depth - 1, nested))
+            },
+            8 => {
+                let object = self.operand(depth - 1, nested, true);
+                match self.random.below(3) {
+                    0 => format!("{}[{}]", object, self.expression(depth - 1, nested)),
+                    1 => format!("{}.{}", object, self.random.pick(NAMES)),
+                    _ => format!("{}?.{}", object, self.random.pick(NAMES)),
+                }
+            },
+            9 => match self.random.below(3) {
+                0 => format!("[{}]", self.list(depth - 1, nested)),
+                1 => format!("({},)", self.expression(depth - 1, nested)),
+                _ => format!("({})", self.list(depth - 1, nested)),
+            },
+            10 => {
+                let value = self.operand(depth - 1, nested, false);
+                let source = match self.random.below(2) {
+                    0 => format!("{} |> {}", value, self.random.pick(NAMES)),
+                    _ => format!("{} |> {}({})", value, self.random.pick(NAMES), self.list(depth - 1, nested)),
+                };
+                return (source, true);
+            },
+            13 => {
+                let condition = self.expression(depth - 1, nested);
+                let consequent = self.expression(depth - 1, nested);
+                let alternative = self.expression(depth - 1, nested);
+                let source = format!("if {} then {} else {}", condition, consequent, alternative);
+                return (source, true);
+            },
+            12 if !nested => match self.random.below(4) {
+                0 => "{:}".to_string(),
+                _ => {
+                    let entries: Vec<String> = (0..1 + self.random.below(3))
+                        .map(|_| format!("{}: {}", self.random.pick(KEYS), self.expression(depth - 1, false)))
+                        .collect();
+                    format!("{{ {} }}", entries.join(", "))
+                },
+            },
+            11 if !nested => {
+                let fields: Vec<String> = (0..self.random.below(3))
+                    .map(|_| format!("{}: {}", self.random.pick(NAMES), self.expression(depth - 1, false)))
+                    .collect();
+                let source = format!("{} {{ {} }}", self.random.pick(NAMES), fields.join(", "));
+                let grouped = self.iterable;
+                self.group(source, grouped)
+            },
+            _ if nested => format!("{}::{}", self.random.pick(NAMES), self.random.pick(NAMES)),
+            _ => {
+                let mut block = String::from("{ ");
+                for _ in 0..self.random.below(3) {
+                    block.push_str(&self.statement(depth - 1));
+                    block.push(' ');
+                }
+                if self.random.below(3) != 0 {
+                    block.push_str(&self.expression(depth - 1, false));
+                }
+                block.push_str(" }");
+                block
+            },
+        };
+        (source, false)
+    }
+
+    /// Generates a `: type` annotation, or nothing.
+    fn annotation(self: &mut Self) -> String {
+        match self.random.below(2) {
+            0 => String::new(),
+            _ => format!(": {}", self.random.pick(TYPES)),
+        }
+    }
+
+    /// Generates a statement along with its `;`, if it takes one.
+    fn statement(self: &mut Self, depth: usize) -> String {
+        let name = self.random.pick(NAMES);
+        match self.random.below(11) {
+            0 => format!("{} {} {};", name, self.random.pick(ASSIGNMENT_OPERATORS), self.expression(depth, false)),
+            1 => format!("{}[{}] = {};", name, self.expression(depth, false), self.expression(depth, false)),
+            2 => format!("{}.{} = {};", name, self.random.pick(NAMES), self.expression(depth, false)),
+            3 => format!("let mut {}{} = {};", name, self.annotation(), self.expression(depth, false)),
+            4 if depth > 0 => {
+                let outer = std::mem::replace(&mut self.iterable, true);
+                let iterable = self.expression(depth - 1, false);
+                self.iterable = outer;
+                let mut body = String::new();
+                for _ in 0..self.random.below(3) {
+                    body.push_str(&self.statement(depth - 1));
+                    body.push(' ');
+                }
+                format!("for {} in {} {{ {}}}", name, iterable, body)
+            },
+            5 => {
+                let fields: Vec<&str> = (0..self.random.below(3)).map(|_| self.random.pick(NAMES)).collect();
+                format!("struct {} {{ {} }}", name, fields.join(", "))
+            },
+            6 => {
+                let variants: Vec<String> = (0..self.random.below(3))
+                    .map(|_| match self.random.below(2) {
+                        0 => self.random.pick(NAMES).to_string(),
+                        _ => format!("{}({})", self.random.pick(NAMES), self.random.pick(NAMES)),
+                    })
+                    .collect();
+                format!("enum {} {{ {} }}", name, variants.join(", "))
+            },
+            7 if depth > 0 => {
+                let parameters: Vec<String> = (0..self.random.below(3))
+                    .map(|_| format!("{}{}", self.random.pick(NAMES), self.annotation()))
+                    .collect();
+                let return_type = match self.random.below(2) {
+                    0 => String::new(),
+                    _ => format!(" -> {}", self.random.pick(TYPES)),
+                };
+                let body = self.expression(depth - 1, false);
+                format!("function {}({}){} {{ {} }}", name, parameters.join(", "), return_type, body)
+            },
+            8 => format!("let {}{} = {};", self.random.pick(PATTERNS), self.annotation(), self.expression(depth, false)),
+            9 => format!("{};", self.expression(depth, false)),
+            _ => format!("let {} = {};", name, self.expression(depth, false)),
+        }
+    }
+
+    fn program(self: &mut Self) -> String {
+        let statements: Vec<String> = (0..1 + self.random.below(3))
+            .map(|_| self.statement(4))
+            .collect();
+        statements.join(" ")
+    }
+}
+
+/// Checks the regression fixtures in `tests/round_trip`.
+#[test]
+fn fixtures() {
+    let mut checked = 0;
+    for entry in fs::read_dir(directory()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension() != Some("bark".as_ref()) {
+            continue;
+        }
+        let source = fs::read(&path).unwrap();
+        assert!(dump(&tokenize(&source).unwrap()).is_some(), "{} does not parse", path.display());
+        if let Err(reason) = check(&source) {
+            panic!("{}: {}", path.display(), reason);
+        }
+        checked += 1;
+    }
+
+    assert!(checked > 0);
+}
+
+/// Round-trips generated programs through `lexer::render`. A failing program
+/// is minimized and saved as a new fixture, so it stays covered once fixed.
+#[test]
+fn generated() {
+    let variable = |name: &str, default: u64| std::env::var(name).ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default);
+    let cases = variable("BARK_FUZZ_CASES", CASES);
+    let first = variable("BARK_FUZZ_SEED", 1);
+
+    for seed in first..first + cases {
+        let mut generator = Generator { random: Random(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1), iterable: false };
+        let source = generator.program();
+        assert!(dump(&tokenize(source.as_bytes()).unwrap()).is_some(), "seed {} generated invalid source: {}", seed, source);
+        if let Err(reason) = check(source.as_bytes()) {
+            let minimized = minimize(source.as_bytes());
+            let path = directory().join(format!("seed-{}.bark", seed));
+            fs::write(&path, &minimized).unwrap();
+            panic!("seed {}: {}; minimized into {}", seed, reason, path.display());
+        }
+    }
+}
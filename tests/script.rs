@@ -0,0 +1,35 @@
+use bark::lexer::{Position, TokenKind};
+use bark::parser;
+
+#[test]
+fn errors() {
+    let script = b"let x = 1;\nlet y = f(x,\n  2;";
+    let error = bark::parse_script(script).unwrap_err();
+    let bark::Error::Parser(parser::Error::UnexpectedToken { found, offset, .. }) = &error else { panic!() };
+    assert_eq!((*found, *offset), (TokenKind::Semicolon, 27));
+    assert_eq!(error.position(script), Position { line: 3, column: 4, offset: 27 });
+
+    let script = b"let x = {\n  let y = (1 + 2\n";
+    let error = bark::parse_script(script).unwrap_err();
+    assert!(matches!(error, bark::Error::Parser(parser::Error::UnclosedDelimiter { delimiter: TokenKind::LeftParenthesis, .. })));
+    assert_eq!(error.position(script), Position { line: 2, column: 11, offset: 20 });
+
+    let error = bark::parse_script(b"let x = 1 +   ").unwrap_err();
+    assert!(error.to_string().starts_with("syntax error: unexpected end of input at offset 14, expected "));
+
+    assert_eq!(bark::parse_script(b"let x = 1;\nx += 2;\n").unwrap().len(), 2);
+}
+
+#[test]
+fn inspect() {
+    let statements = bark::parse_script(b"let total = price * (1 + rate);").unwrap();
+    let [parser::Statement::Let(declaration)] = statements.as_slice() else { panic!() };
+    assert_eq!(declaration.pattern, parser::Pattern::Identifier(b"total".to_vec()));
+    let parser::Expression::BinaryMultiplication(product) = &declaration.value else { panic!() };
+    assert_eq!(product.left_operand, parser::Expression::Identifier(Box::new(b"price".to_vec())));
+    let parser::Expression::BinaryAddition(sum) = &product.right_operand else { panic!() };
+    assert!(matches!(&sum.right_operand, parser::Expression::Identifier(name) if **name == b"rate"));
+
+    let copy = statements.clone();
+    assert_eq!(copy, statements);
+}
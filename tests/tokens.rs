@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::Path;
+use bark::lexer::{StringSegment, Token};
+
+/// Describes the decoded payload of literal tokens, which their source text
+/// alone doesn't show.
+fn value(token: &Token) -> Option<String> {
+    let value = match token {
+        Token::Integer(integer) => format!("{:?}", integer.to_i128()?),
+        Token::Float(float) => format!("{:?}", float.to_f64()?),
+        Token::Character(character) => format!("{:?}", character),
+        Token::String(string) => format!("{:?}", String::from_utf8_lossy(string)),
+        Token::InterpolatedString(segments) => segments.iter()
+            .map(|segment| match segment {
+                StringSegment::Literal(literal) => format!("{:?}", String::from_utf8_lossy(literal)),
+                StringSegment::Tokens(tokens) => {
+                    let kinds: Vec<String> = tokens.iter().map(|token| format!("{:?}", token.kind())).collect();
+                    format!("${{{}}}", kinds.join(" "))
+                },
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Renders one token per line: its byte span, kind and source text, then the
+/// value of literals.
+fn snapshot(script: &[u8]) -> String {
+    let mut output = String::new();
+    let mut offset = 0;
+    for token in bark::lexer::tokenize_with_trivia(script).unwrap() {
+        let start = offset + token.leading.len();
+        let end = start + token.text.len();
+        let text = String::from_utf8_lossy(&token.text);
+        output.push_str(&format!("{}..{} {:?} {:?}", start, end, token.token.kind(), text));
+        if let Some(value) = value(&token.token) {
+            output.push_str(&format!(" = {}", value));
+        }
+        output.push('\n');
+        offset = end + token.trailing.len();
+    }
+    output
+}
+
+/// Lists the lines that differ between two snapshots.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut output = String::new();
+    for line in 0..expected.len().max(actual.len()) {
+        let (old, new) = (expected.get(line), actual.get(line));
+        if old == new {
+            continue;
+        }
+        output.push_str(&format!("line {}:\n", line + 1));
+        if let Some(old) = old {
+            output.push_str(&format!("- {}\n", old));
+        }
+        if let Some(new) = new {
+            output.push_str(&format!("+ {}\n", new));
+        }
+    }
+    output
+}
+
+/// Compares the token stream of every `tests/tokens/*.bark` script against
+/// the `.tokens` file next to it. Set `BARK_BLESS=1` to rewrite the snapshots
+/// after an intentional change.
+#[test]
+fn snapshots() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/tokens");
+    let bless = std::env::var_os("BARK_BLESS").is_some();
+    let mut failures = String::new();
+    let mut checked = 0;
+
+    for entry in fs::read_dir(&directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension() != Some("bark".as_ref()) {
+            continue;
+        }
+
+        let actual = snapshot(&fs::read(&path).unwrap());
+        let expected_path = path.with_extension("tokens");
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+        } else {
+            let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+            if actual != expected {
+                failures.push_str(&format!("{}:\n{}", expected_path.display(), diff(&expected, &actual)));
+            }
+        }
+        checked += 1;
+    }
+
+    assert!(checked > 0);
+    assert!(failures.is_empty(), "token snapshots changed, rerun with BARK_BLESS=1 if intended\n{}", failures);
+}